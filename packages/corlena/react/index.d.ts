@@ -52,17 +52,35 @@ export function Resizable(props: {
 
 // Overlay types
 export type Transform = { x: number; y: number; angle: number; sx: number; sy: number };
+// Positions are world coordinates: the pointer for taps/long-press, the node's top-left for drags.
 export type DomNodeHandlers = {
-  onTap?: (id: number) => void;
-  onDoubleTap?: (id: number) => void;
-  onDragStart?: (id: number) => void;
-  onDragEnd?: (id: number) => void;
+  onTap?: (id: number, x: number, y: number) => void;
+  onDoubleTap?: (id: number, x: number, y: number) => void;
+  onLongPress?: (id: number, x: number, y: number) => void;
+  /** Three or more taps in a row, up to the configured max_taps. */
+  onMultiTap?: (id: number, count: number, x: number, y: number) => void;
+  /** direction: 0 = right, 1 = down, 2 = left, 3 = up; speed in world px/s. */
+  onSwipe?: (id: number, direction: number, speed: number) => void;
+  onDragStart?: (id: number, x: number, y: number) => void;
+  /** Once per frame the node moved; dx/dy since the previous report. */
+  onDragMove?: (id: number, x: number, y: number, dx: number, dy: number) => void;
+  /** vx/vy: pointer velocity at release. */
+  onDragEnd?: (id: number, x: number, y: number, vx: number, vy: number) => void;
+  onDropEnter?: (id: number, zoneId: number) => void;
+  onDropLeave?: (id: number, zoneId: number) => void;
+  /** x/y: pointer position at release. */
+  onDrop?: (id: number, zoneId: number, x: number, y: number) => void;
+  /** prop: AnimProp code; cancelled when a grab or a newer animation took over. */
+  onAnimEnd?: (id: number, prop: number, cancelled: boolean) => void;
 };
 export type SceneContextValue = {
   ready: boolean;
   upsertNode: (node: { id: number; x?: number; y?: number; w?: number; h?: number; vx?: number; vy?: number; flags?: number }) => void;
+  /** Update only the node's size; an in-progress drag continues. */
+  resizeNode: (id: number, w: number, h: number) => void;
+  removeNode: (id: number) => void;
   registerHandlers: (id: number, handlers?: DomNodeHandlers) => void | (() => void);
-  applyPointer: (id: number, x: number, y: number, buttons: number) => void;
+  applyPointer: (pointerId: number, x: number, y: number, buttons: number) => void;
   getTransform: (id: number) => Transform | null;
   layerRef: React.RefObject<HTMLDivElement>;
   toLocal: (clientX: number, clientY: number) => { x: number; y: number };
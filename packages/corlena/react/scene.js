@@ -5,6 +5,12 @@ import React, { createContext, useCallback, useContext, useEffect, useMemo, useR
 
 const SceneContext = createContext(null);
 
+// Layout sync (mount, measure, unmount) mirrors the DOM; it is not an undoable edit
+function untracked(wasm, fn) {
+  wasm.pauseHistory?.(true);
+  try { fn(); } finally { wasm.pauseHistory?.(false); }
+}
+
 export function useScene() {
   const ctx = useContext(SceneContext);
   if (!ctx) throw new Error('useScene must be used inside <SceneProvider>');
@@ -17,24 +23,34 @@ export function SceneProvider({ children, tapParams, capacity = 256 }) {
   const rafRef = useRef(0);
   const [ready, setReady] = useState(false);
   const transformsRef = useRef(new Map()); // id -> { x, y, sx, sy, angle }
-  const listenersRef = useRef(new Map());   // id -> { onTap, onDoubleTap, onDragStart, onDragEnd }
+  const listenersRef = useRef(new Map());   // id -> { onTap, onDoubleTap, onLongPress, onMultiTap, onSwipe, onDragStart, onDragMove, onDragEnd, onDropEnter, onDropLeave, onDrop, onAnimEnd }
   const pixelRatio = (typeof window !== 'undefined' && window.devicePixelRatio) ? window.devicePixelRatio : 1;
   const layerRef = useRef(null);
 
   // Drain events to listeners
-  const dispatchEvents = useCallback((arr) => {
+  const dispatchEvents = useCallback((arr, data) => {
     if (!arr || arr.length === 0) return;
-    const stride = 4; // [type, a, b, data]
-    for (let i = 0; i + 3 < arr.length; i += stride) {
+    const stride = 4; // [type, a, b, data]; float payload at the same offsets in `data`
+    const f = data && data.length === arr.length ? data : new Float32Array(arr.length);
+    // Skip the [0, version, stride, count] header record
+    for (let i = arr[0] === 0 ? stride : 0; i + 3 < arr.length; i += stride) {
       const type = arr[i];
       const a = arr[i + 1];
-      // const b = arr[i + 2];
+      const b = arr[i + 2];
       const h = listenersRef.current.get(a);
       if (!h) continue;
-      if (type === 1) h.onDragStart?.(a);
-      else if (type === 2) h.onDragEnd?.(a);
-      else if (type === 10) h.onTap?.(a);
-      else if (type === 11) h.onDoubleTap?.(a);
+      if (type === 1) h.onDragStart?.(a, f[i], f[i + 1]);
+      else if (type === 2) h.onDragEnd?.(a, f[i], f[i + 1], f[i + 2], f[i + 3]);
+      else if (type === 3) h.onDragMove?.(a, f[i], f[i + 1], f[i + 2], f[i + 3]);
+      else if (type === 10) h.onTap?.(a, f[i], f[i + 1]);
+      else if (type === 11) h.onDoubleTap?.(a, f[i], f[i + 1]);
+      else if (type === 12) h.onLongPress?.(a, f[i], f[i + 1]);
+      else if (type === 13) h.onMultiTap?.(a, b, f[i], f[i + 1]);
+      else if (type === 14) h.onSwipe?.(a, b, arr[i + 3]);
+      else if (type === 50) h.onDropEnter?.(a, b);
+      else if (type === 51) h.onDropLeave?.(a, b);
+      else if (type === 52) h.onDrop?.(a, b, f[i + 2], f[i + 3]);
+      else if (type === 60) h.onAnimEnd?.(a, b, arr[i + 3] === 1);
     }
   }, []);
 
@@ -58,9 +74,10 @@ export function SceneProvider({ children, tapParams, capacity = 256 }) {
       }
     }
     const ev = out.events;
-    if (ev && ev.length) {
+    // Header-only frames carry no events
+    if (ev && ev.length && !(ev[0] === 0 && ev.length === 4)) {
       console.log('Events:', ev);
-      dispatchEvents(ev);
+      dispatchEvents(ev, out.eventData);
     }
   }, [dispatchEvents]);
 
@@ -97,7 +114,20 @@ export function SceneProvider({ children, tapParams, capacity = 256 }) {
     if (!wasm) return;
     const { id, x = 0, y = 0, w = 100, h = 60, vx = 0, vy = 0, flags = 0 } = node || {};
     const buf = new Float32Array([id, x, y, w, h, vx, vy, flags]);
-    wasm.upsertNodes?.(buf);
+    untracked(wasm, () => wasm.upsertNodes?.(buf));
+  }, []);
+
+  // Size-only update: keeps an in-progress drag, unlike a re-upsert
+  const resizeNode = useCallback((id, w, h) => {
+    const wasm = wasmRef.current;
+    if (!wasm) return;
+    untracked(wasm, () => wasm.patchNodes?.(wasm.PatchField?.SIZE ?? 2, new Float32Array([id, w, h])));
+  }, []);
+
+  const removeNode = useCallback((id) => {
+    const wasm = wasmRef.current;
+    if (!wasm) return;
+    untracked(wasm, () => wasm.removeNodes?.([id]));
   }, []);
 
   const registerHandlers = useCallback((id, handlers) => {
@@ -107,11 +137,11 @@ export function SceneProvider({ children, tapParams, capacity = 256 }) {
     return () => map.delete(id);
   }, []);
 
-  const applyPointer = useCallback((id, x, y, buttons) => {
+  const applyPointer = useCallback((pointerId, x, y, buttons) => {
     const wasm = wasmRef.current;
     if (!wasm) return;
-    // stride=5 variant: [id, x, y, pressure, buttons]
-    const arr = new Float32Array([id, x, y, buttons > 0 ? 0.7 : 0.0, buttons]);
+    // stride=5 variant: [pointerId, x, y, pressure, buttons]; the engine hit-tests nodes itself
+    const arr = new Float32Array([pointerId, x, y, buttons > 0 ? 0.7 : 0.0, buttons]);
     wasm.applyPointers?.(arr);
   }, []);
 
@@ -124,7 +154,7 @@ export function SceneProvider({ children, tapParams, capacity = 256 }) {
     return { x: clientX - r.left, y: clientY - r.top };
   }, []);
 
-  const value = useMemo(() => ({ ready, upsertNode, registerHandlers, applyPointer, getTransform, layerRef, toLocal }), [ready, upsertNode, registerHandlers, applyPointer, getTransform, toLocal]);
+  const value = useMemo(() => ({ ready, upsertNode, resizeNode, removeNode, registerHandlers, applyPointer, getTransform, layerRef, toLocal }), [ready, upsertNode, resizeNode, removeNode, registerHandlers, applyPointer, getTransform, toLocal]);
 
   return React.createElement(SceneContext.Provider, 
     { value },
@@ -150,15 +180,15 @@ export function DomLayer({ style, className, children }) {
   );
 }
 
-export function DomNode({ id, children, style, className, onTap, onDoubleTap, onDragStart, onDragEnd }) {
-  const { registerHandlers, upsertNode, applyPointer, getTransform, toLocal } = useScene();
+export function DomNode({ id, children, style, className, onTap, onDoubleTap, onLongPress, onMultiTap, onSwipe, onDragStart, onDragMove, onDragEnd, onDropEnter, onDropLeave, onDrop, onAnimEnd }) {
+  const { registerHandlers, upsertNode, resizeNode, removeNode, applyPointer, getTransform, toLocal } = useScene();
   const ref = useRef(null);
   const [css, setCss] = useState({ transform: 'translate3d(0px,0px,0px) scale(1,1)', transformOrigin: '0 0' });
   const isDraggingRef = useRef(false);
   const dragOffsetRef = useRef({ x: 0, y: 0 });
 
   // Register event handlers
-  useEffect(() => registerHandlers(id, { onTap, onDoubleTap, onDragStart, onDragEnd }), [id, onTap, onDoubleTap, onDragStart, onDragEnd, registerHandlers]);
+  useEffect(() => registerHandlers(id, { onTap, onDoubleTap, onLongPress, onMultiTap, onSwipe, onDragStart, onDragMove, onDragEnd, onDropEnter, onDropLeave, onDrop, onAnimEnd }), [id, onTap, onDoubleTap, onLongPress, onMultiTap, onSwipe, onDragStart, onDragMove, onDragEnd, onDropEnter, onDropLeave, onDrop, onAnimEnd, registerHandlers]);
 
   // Measure node and upsert
   useEffect(() => {
@@ -166,16 +196,15 @@ export function DomNode({ id, children, style, className, onTap, onDoubleTap, on
     if (!el) return;
     const ro = new (window.ResizeObserver || class { observe(){} disconnect(){} })(() => {
       const r = el.getBoundingClientRect();
-      const { x, y } = toLocal(r.left, r.top);
-      upsertNode({ id, x, y, w: r.width, h: r.height });
+      resizeNode(id, r.width, r.height);
     });
     try { ro.observe(el); } catch {}
     // initial
     const r = el.getBoundingClientRect();
     const { x, y } = toLocal(r.left, r.top);
     upsertNode({ id, x, y, w: r.width, h: r.height });
-    return () => { try { ro.disconnect(); } catch {} };
-  }, [id, toLocal, upsertNode]);
+    return () => { try { ro.disconnect(); } catch {} removeNode(id); };
+  }, [id, toLocal, upsertNode, resizeNode, removeNode]);
 
   // Animate CSS transform from WASM (only when not actively dragging)
   useEffect(() => {
@@ -209,8 +238,8 @@ export function DomNode({ id, children, style, className, onTap, onDoubleTap, on
     dragOffsetRef.current = { x: p.x - nodeTopLeft.x, y: p.y - nodeTopLeft.y };
     isDraggingRef.current = true;
     
-    applyPointer(id, p.x, p.y, 1);
-  }, [applyPointer, id, toLocal]);
+    applyPointer(e.pointerId, p.x, p.y, 1);
+  }, [applyPointer, toLocal]);
 
   const onPointerMove = useCallback((e) => {
     if (e.buttons === 0) return;
@@ -225,26 +254,15 @@ export function DomNode({ id, children, style, className, onTap, onDoubleTap, on
       setCss(prev => ({ ...prev, transform: tr }));
     }
     
-    applyPointer(id, p.x, p.y, e.buttons);
-  }, [applyPointer, id, toLocal]);
+    applyPointer(e.pointerId, p.x, p.y, e.buttons);
+  }, [applyPointer, toLocal]);
 
   const onPointerUp = useCallback((e) => {
     const p = toLocal(e.clientX, e.clientY);
-    
-    if (isDraggingRef.current) {
-      // Update WASM with final position before releasing drag
-      const targetX = p.x - dragOffsetRef.current.x;
-      const targetY = p.y - dragOffsetRef.current.y;
-      const el = ref.current;
-      if (el) {
-        const rect = el.getBoundingClientRect();
-        upsertNode({ id, x: targetX, y: targetY, w: rect.width, h: rect.height });
-      }
-    }
-    
+    // The engine already tracked the drag; re-upserting here would cancel it (no drag_end, throw or drop)
     isDraggingRef.current = false;
-    applyPointer(id, p.x, p.y, 0);
-  }, [applyPointer, id, toLocal, upsertNode]);
+    applyPointer(e.pointerId, p.x, p.y, 0);
+  }, [applyPointer, toLocal]);
 
   const mergedStyle = { position: 'absolute', touchAction: 'none', userSelect: 'none', ...css, ...style };
 
@@ -3,9 +3,18 @@ import { SvelteComponentTyped } from 'svelte';
 export interface DomNodeProps {
   id: number;
   style?: Record<string, any>;
-  onTap?: (nodeId: number) => void;
-  onDragStart?: (nodeId: number) => void;
-  onDragEnd?: (nodeId: number) => void;
+  onTap?: (nodeId: number, x: number, y: number) => void;
+  onDoubleTap?: (nodeId: number, x: number, y: number) => void;
+  onLongPress?: (nodeId: number, x: number, y: number) => void;
+  onMultiTap?: (nodeId: number, count: number, x: number, y: number) => void;
+  onSwipe?: (nodeId: number, direction: number, speed: number) => void;
+  onDragStart?: (nodeId: number, x: number, y: number) => void;
+  onDragMove?: (nodeId: number, x: number, y: number, dx: number, dy: number) => void;
+  onDragEnd?: (nodeId: number, x: number, y: number, vx: number, vy: number) => void;
+  onDropEnter?: (nodeId: number, zoneId: number) => void;
+  onDropLeave?: (nodeId: number, zoneId: number) => void;
+  onDrop?: (nodeId: number, zoneId: number, x: number, y: number) => void;
+  onAnimEnd?: (nodeId: number, prop: number, cancelled: boolean) => void;
 }
 
 export interface DomNodeSlots {
@@ -2,6 +2,12 @@
 import { writable, derived, get } from 'svelte/store';
 import { onMount, onDestroy } from 'svelte';
 
+// Layout sync (mount, measure, unmount) mirrors the DOM; it is not an undoable edit
+function untracked(wasm, fn) {
+  wasm.pauseHistory?.(true);
+  try { fn(); } finally { wasm.pauseHistory?.(false); }
+}
+
 // Scene store - manages WASM and global state
 export function createScene(options = {}) {
   const { capacity = 256, pixelRatio = 1, tapParams } = options;
@@ -35,22 +41,32 @@ export function createScene(options = {}) {
       }
     }
     const ev = out.events;
-    if (ev && ev.length) dispatchEvents(ev);
+    if (ev && ev.length) dispatchEvents(ev, out.eventData);
   };
 
   // Event dispatcher
-  const dispatchEvents = (events) => {
-    const stride = 4; // [type, a, b, data]
-    for (let i = 0; i + 3 < events.length; i += stride) {
+  const dispatchEvents = (events, data) => {
+    const stride = 4; // [type, a, b, data]; float payload at the same offsets in `data`
+    const f = data && data.length === events.length ? data : new Float32Array(events.length);
+    // Skip the [0, version, stride, count] header record
+    for (let i = events[0] === 0 ? stride : 0; i + 3 < events.length; i += stride) {
       const type = events[i] | 0;
       const nodeId = events[i + 1] | 0;
       const handlers = listenersRef.current.get(nodeId);
       if (!handlers) continue;
       
-      if (type === 1 && handlers.onDragStart) handlers.onDragStart(nodeId);
-      else if (type === 2 && handlers.onDragEnd) handlers.onDragEnd(nodeId);
-      else if (type === 10 && handlers.onTap) handlers.onTap(nodeId);
-      else if (type === 11 && handlers.onDoubleTap) handlers.onDoubleTap(nodeId);
+      if (type === 1 && handlers.onDragStart) handlers.onDragStart(nodeId, f[i], f[i + 1]);
+      else if (type === 2 && handlers.onDragEnd) handlers.onDragEnd(nodeId, f[i], f[i + 1], f[i + 2], f[i + 3]);
+      else if (type === 3 && handlers.onDragMove) handlers.onDragMove(nodeId, f[i], f[i + 1], f[i + 2], f[i + 3]);
+      else if (type === 10 && handlers.onTap) handlers.onTap(nodeId, f[i], f[i + 1]);
+      else if (type === 11 && handlers.onDoubleTap) handlers.onDoubleTap(nodeId, f[i], f[i + 1]);
+      else if (type === 12 && handlers.onLongPress) handlers.onLongPress(nodeId, f[i], f[i + 1]);
+      else if (type === 13 && handlers.onMultiTap) handlers.onMultiTap(nodeId, events[i + 2] | 0, f[i], f[i + 1]);
+      else if (type === 14 && handlers.onSwipe) handlers.onSwipe(nodeId, events[i + 2] | 0, events[i + 3] | 0);
+      else if (type === 50 && handlers.onDropEnter) handlers.onDropEnter(nodeId, events[i + 2] | 0);
+      else if (type === 51 && handlers.onDropLeave) handlers.onDropLeave(nodeId, events[i + 2] | 0);
+      else if (type === 52 && handlers.onDrop) handlers.onDrop(nodeId, events[i + 2] | 0, f[i + 2], f[i + 3]);
+      else if (type === 60 && handlers.onAnimEnd) handlers.onAnimEnd(nodeId, events[i + 2] | 0, events[i + 3] === 1);
     }
   };
 
@@ -80,7 +96,20 @@ export function createScene(options = {}) {
     if (!wasm) return;
     const { id, x = 0, y = 0, w = 100, h = 60, vx = 0, vy = 0, flags = 0 } = node || {};
     const buf = new Float32Array([id, x, y, w, h, vx, vy, flags]);
-    wasm.upsertNodes?.(buf);
+    untracked(wasm, () => wasm.upsertNodes?.(buf));
+  };
+
+  // Size-only update: keeps an in-progress drag, unlike a re-upsert
+  const resizeNode = (id, w, h) => {
+    const wasm = wasmRef.current;
+    if (!wasm) return;
+    untracked(wasm, () => wasm.patchNodes?.(wasm.PatchField?.SIZE ?? 2, new Float32Array([id, w, h])));
+  };
+
+  const removeNode = (id) => {
+    const wasm = wasmRef.current;
+    if (!wasm) return;
+    untracked(wasm, () => wasm.removeNodes?.([id]));
   };
 
   const registerHandlers = (id, handlers) => {
@@ -90,10 +119,11 @@ export function createScene(options = {}) {
     return () => map.delete(id);
   };
 
-  const applyPointer = (id, x, y, buttons) => {
+  const applyPointer = (pointerId, x, y, buttons) => {
     const wasm = wasmRef.current;
     if (!wasm) return;
-    const arr = new Float32Array([id, x, y, buttons > 0 ? 0.7 : 0.0, buttons]);
+    // [pointerId, x, y, pressure, buttons]; the engine hit-tests nodes itself
+    const arr = new Float32Array([pointerId, x, y, buttons > 0 ? 0.7 : 0.0, buttons]);
     wasm.applyPointers?.(arr);
   };
 
@@ -119,6 +149,8 @@ export function createScene(options = {}) {
     init,
     destroy,
     upsertNode,
+    resizeNode,
+    removeNode,
     registerHandlers,
     applyPointer,
     getTransform,
@@ -1,24 +1,277 @@
+export interface CanvasComposition {
+  version: string;
+  canvas: { width: number | null; height: number | null; background: string };
+  /** Colors are CSS `rgba(...)` strings. */
+  drawPaths: { id: number; points: { x: number; y: number; pressure: number }[]; color: string; width: number; closed: boolean }[];
+  textLayers: unknown[];
+  /** One per stored image, on the node with the same id (else at the origin, pixel-sized); `url` is left empty for the app. */
+  imageLayers: { id: number; url: string; x: number; y: number; w: number; h: number }[];
+  settings: {
+    particlesEnabled: boolean;
+    view: { scale: number; panX: number; panY: number; pixelRatio: number };
+    /** Unbounded edges are null. */
+    constraints: { left: number | null; top: number | null; right: number | null; bottom: number | null; gridX: number; gridY: number; inertia: number; damping: number };
+    /** Engine nodes, in stacking order. */
+    nodes: {
+      id: number; x: number; y: number; w: number; h: number; flags: number; angle: number;
+      scaleX: number; scaleY: number; pivotX: number; pivotY: number; z: number; parent: number | null;
+    }[];
+  };
+}
+
 export interface FrameOut {
+  /**
+   * Stride 7 per node: [id, x, y, angle, scaleX, scaleY, reserved]
+   * In stacking order, bottom to top. angle is radians about the node pivot;
+   * scaleX/Y = view scale * node scale.
+   */
   transforms: Float32Array;
+  /** Stride 3 per node: [id, w, h], same order as transforms (unscaled size; changes when resized). */
+  sizes: Float32Array;
+  /**
+   * Stride 7 per node: [id, parentId, x, y, angle, scaleX, scaleY]
+   * Relative to the parent's unrotated, unscaled box; parentId -1 for roots (same as world).
+   */
+  locals: Float32Array;
   particles: Float32Array;
+  /**
+   * Smart guides active this frame, stride 4: [axis, position, nodeId, otherNodeId]
+   * axis 0 = vertical line at world x, 1 = horizontal line at world y.
+   */
+  guides: Float32Array;
+  /**
+   * View after this frame: [scale, panX, panY, pixelRatio]; CSS `matrix(scale, 0, 0, scale, panX, panY)`
+   * maps world to CSS px. It changes with autoscroll, pan inertia and camera animations,
+   * so apply it instead of keeping your own copy.
+   */
+  view: Float32Array;
   /**
    * Events ring buffer with stride of 4 per event: [type, a, b, data]
-   * Types:
+   * The first record is a header [0, schemaVersion, 4, count]; use decodeEvents() to check it.
+   * Types (see EventType):
    *  1 = drag_start (a=nodeId)
    *  2 = drag_end   (a=nodeId)
+   *  3 = drag_move  (a=nodeId, b=pointerId; at most once per frame)
    * 10 = tap        (a=nodeId, b=1)
    * 11 = double_tap (a=nodeId, b=2)
+   * 12 = long_press (a=nodeId, b=pointerId)
+   * 13 = multi_tap  (a=nodeId, b=count >= 3)
+   * 14 = swipe      (a=nodeId or -1, b=direction 0=right 1=down 2=left 3=up, data=speed world px/s)
+   * 20 = pinch_start (a=nodeId, b=pointerA, data=pointerB)
+   * 21 = pinch_end   (a=nodeId)
+   * 30 = collision_start (a=nodeId, b=otherNodeId; a < b)
+   * 31 = collision_end   (a=nodeId, b=otherNodeId; a < b)
+   * 40 = resize_start (a=nodeId, b=handle)
+   * 41 = resize_end   (a=nodeId, b=handle)
+   * Resize handles: 0=nw, 1=n, 2=ne, 3=e, 4=se, 5=s, 6=sw, 7=w
+   * 50 = drop_enter (a=nodeId, b=zoneId)
+   * 51 = drop_leave (a=nodeId, b=zoneId)
+   * 52 = drop       (a=nodeId, b=zoneId; ends the hover, no drop_leave follows)
+   * 60 = anim_end   (a=nodeId, b=AnimProp, data=1 when cancelled by a grab or a newer animation)
    */
   events: Int32Array;
+  /**
+   * Float payload per event, index-aligned with `events` including the header (stride 4), world space:
+   *  drag_start          [nodeX, nodeY, pointerX, pointerY]
+   *  drag_move           [nodeX, nodeY, dx, dy]
+   *  drag_end            [nodeX, nodeY, releaseVx, releaseVy]
+   *  tap/double/multi, long_press  [x, y]
+   *  swipe               [vx, vy]
+   *  resize_start/end    [x, y, w, h]
+   *  drop                [nodeX, nodeY, pointerX, pointerY]
+   *  anim_end            [value]
+   * Other events carry zeros.
+   */
+  eventData: Float32Array;
 }
+export const EVENT_SCHEMA_VERSION: number;
+export const EventType: Readonly<{
+  DRAG_START: 1; DRAG_END: 2; DRAG_MOVE: 3;
+  TAP: 10; DOUBLE_TAP: 11; LONG_PRESS: 12; MULTI_TAP: 13; SWIPE: 14;
+  PINCH_START: 20; PINCH_END: 21;
+  COLLISION_START: 30; COLLISION_END: 31;
+  RESIZE_START: 40; RESIZE_END: 41;
+  DROP_ENTER: 50; DROP_LEAVE: 51; DROP: 52;
+  ANIM_END: 60;
+}>;
+export interface EventSchema {
+  version: number;
+  stride: number;
+  header: string[];
+  /** fields name [a, b, data]; payload names the eventData floats ('' = unused). */
+  types: { code: number; name: string; fields: string[]; payload: string[] }[];
+}
+export interface DecodedEvent {
+  type: number;
+  /** Lower-case EventType key, e.g. 'drag_start'; 'unknown' for codes this wrapper predates. */
+  name: string;
+  a: number;
+  b: number;
+  data: number;
+  payload: [number, number, number, number];
+}
+/** Schema of the loaded wasm module, or null before init. */
+export function eventSchema(): EventSchema | null;
+/** Decode events/eventData (header optional); throws on a schema version mismatch. */
+export function decodeEvents(events: Int32Array, eventData?: Float32Array): DecodedEvent[];
+/** Load the wasm module; rejects (and stays not ready) if its event schema differs from EVENT_SCHEMA_VERSION. */
 export function init(capacity?: number): Promise<void>;
 export function reset(): void;
 export function setView(scale: number): void;
 export function setViewParams(scale: number, panX: number, panY: number, pixelRatio: number): void;
+/** Viewport size in CSS px (same units as pan); 0 = unknown, which disables autoscroll. */
+export function setViewport(width: number, height: number): void;
+/**
+ * Configure autoscroll: [margin, maxSpeed]. While a node is dragged or resized with its
+ * pointer within `margin` CSS px of a viewport edge, the view pans at up to `maxSpeed`
+ * CSS px/s (proportional to depth into the margin) and the node stays under the pointer.
+ * margin 0 disables.
+ */
+export function setAutoscrollParams(params: Float32Array | number[]): void;
+/**
+ * Configure the camera: [minZoom, maxZoom, panDamping]. Zoom defaults to 0.1x–10x;
+ * panDamping is the fraction of fling velocity kept after one second (default 0.05).
+ */
+export function setCameraParams(params: Float32Array | number[]): void;
+/** Keep the viewport inside this world rect (needs setViewport); smaller content is centered. */
+export function setCameraBounds(l: number, t: number, r: number, b: number): void;
+export function clearCameraBounds(): void;
+/** Multiply the zoom, keeping the world point under screen (sx, sy) fixed (pointer coordinates). */
+export function zoomAt(factor: number, sx: number, sy: number): void;
+/** Move the content by (dx, dy) CSS px, e.g. from wheel deltas negated or a two-finger drag. */
+export function panCamera(dx: number, dy: number): void;
+/** End a pan gesture; the camera coasts with the recent pan velocity. Any press stops it. */
+export function releaseCameraPan(): void;
+/** Ease the view over `duration` seconds (0 = jump) to fit a world rect with `padding` CSS px. */
+export function zoomToRect(l: number, t: number, r: number, b: number, padding?: number, duration?: number): boolean;
+/** zoomToRect over the union of the nodes' boxes; empty ids fits every node. */
+export function fitToNodes(ids: Int32Array | number[], padding?: number, duration?: number): boolean;
 export function setConstraints(params: Float32Array): void;
+/**
+ * Node records `[id, x, y, w, h, vx, vy, flags] * N`.
+ * Flags: bit 0 = no hit-test, bit 1 = collide, bit 2 = pinned (not pushed by collisions),
+ * bit 3 = resizable (edge/corner handles), bit 4 = lock aspect ratio while resizing.
+ * Re-upserting an existing node keeps its in-flight drag, pinch, resize, drop hover and taps.
+ */
 export function upsertNodes(nodes: Float32Array): void;
+/**
+ * Node records with per-node transform:
+ * `[id, x, y, w, h, vx, vy, flags, angle, scaleX, scaleY, pivotX, pivotY] * N`.
+ * Pivot is a fraction of w/h (0.5, 0.5 = center).
+ */
+export function upsertNodesV2(nodes: Float32Array): void;
+export const PatchField: Readonly<{ POSITION: 1; SIZE: 2; VELOCITY: 4; FLAGS: 8; TRANSFORM: 16; PIVOT: 32 }>;
+/**
+ * Update only the chosen fields of existing nodes: records are `[id, ...values] * N`, with the
+ * values of each set PatchField group in the order above (e.g. POSITION | FLAGS = [id, x, y, flags]).
+ * Unlike upserts, fields outside the chosen groups keep their values. Returns how many nodes were patched.
+ */
+export function patchNodes(fields: number, data: Float32Array | number[]): number;
+/** Remove nodes; their children stay in place as roots. Returns how many were removed. */
+export function removeNodes(ids: Int32Array | number[]): number;
+export function clearNodes(): void;
+/**
+ * Pointer records `[pointerId, x, y, pressure?, buttons]` in screen px.
+ * A press hit-tests nodes and captures the topmost one; moves and the release
+ * route to the captured node until the pointer goes up.
+ */
 export function applyPointers(pointers: Float32Array): void;
+/** Topmost node id under the point, or -1. `screen` (default true) takes screen px like applyPointers. */
+export function hitTest(x: number, y: number, screen?: boolean): number;
+/** Ids of nodes whose bounding box overlaps the rect, in stacking order. */
+export function queryRect(l: number, t: number, r: number, b: number, screen?: boolean): Int32Array;
+/**
+ * Screen px (the space applyPointers takes) to world, using the engine's current view:
+ * world = (screen / pixelRatio - pan) / scale. Identity before init.
+ */
+export function screenToWorld(x: number, y: number): { x: number; y: number };
+export function worldToScreen(x: number, y: number): { x: number; y: number };
+/** Batch conversions over `[x, y] * N`; a trailing odd value is dropped. */
+export function screenToWorldBatch(points: Float32Array | number[]): Float32Array;
+export function worldToScreenBatch(points: Float32Array | number[]): Float32Array;
+// Hierarchy APIs: children move, rotate and scale with their parent (a thrown child glides in its parent's frame)
+/** Attach `child` under `parent` (null/-1 detaches) without moving it; false on cycles or unknown ids. */
+export function setParent(child: number, parent: number | null): boolean;
+/** Attach each of `ids` under `parent`; returns how many were attached. */
+export function groupNodes(parent: number, ids: Int32Array | number[]): number;
+/** Detach all children of `parent`, keeping their world transforms. */
+export function ungroupNodes(parent: number): number;
+// Drop zone APIs: a dragged node hovers the topmost (last registered) accepting zone
+/**
+ * Zone records `[id, x, y, w, h, acceptMask, mode] * Z` in world px. A node is accepted when
+ * `flags & acceptMask` is non-zero (0 accepts all); mode 0 hits by pointer, 1 by node box overlap.
+ */
+export function upsertDropZones(zones: Float32Array | number[]): void;
+/** Remove a zone; nodes hovering it get drop_leave. */
+export function removeDropZone(id: number): boolean;
+export function clearDropZones(): void;
+// Animation APIs: node animations advance in processFrame; a grab cancels them
+export const AnimProp: Readonly<{ X: 0; Y: 1; W: 2; H: 3; ANGLE: 4; SCALE: 5 }>;
+export const Easing: Readonly<{ LINEAR: 0; EASE_IN_OUT: 1; EASE_OUT: 2; EASE_IN: 3 }>;
+/**
+ * Tween a node property (AnimProp; SCALE is uniform) to `target` over `duration` seconds.
+ * Replaces a running animation of the same property; false for unknown or held nodes.
+ */
+export function animateNode(id: number, prop: number, target: number, duration: number, easing?: number): boolean;
+/**
+ * Spring a node property to `target` (per unit mass; defaults 170 / 26). False unless stiffness > 0;
+ * a spring still moving after 10 s jumps to the target and ends.
+ */
+export function springNode(id: number, prop: number, target: number, stiffness?: number, damping?: number): boolean;
+/** Stop the node's animations where they are; returns how many were running. */
+export function cancelNodeAnimations(id: number): number;
+// History APIs. One undo step per node gesture (press until the released nodes are at rest:
+// throws coasted out, grid snapped), per stroke (startDrawPath to finishDrawPath) and per
+// upsert/hierarchy/z-order/path edit.
+/** Undo the last step (closing a settling gesture first); false when there is none or a step is still open. */
+export function undo(): boolean;
+export function redo(): boolean;
+export function canUndo(): boolean;
+export function canRedo(): boolean;
+/** Merge everything until the matching endHistoryGroup into one step (groups nest). */
+export function beginHistoryGroup(): void;
+export function endHistoryGroup(): void;
+/** Max undo steps kept (default 100; 0 turns recording off). */
+export function setHistoryDepth(depth: number): void;
+/** While paused, edits are applied but not recorded; for layout sync (mount, measure, unmount). */
+export function pauseHistory(paused: boolean): void;
+export function clearHistory(): void;
+// Snapshot APIs
+/** Nodes, draw paths, particles, images, view and constraints in a versioned binary format. */
+export function saveState(): Uint8Array;
+/** Replace the scene with a saveState buffer; false (scene untouched) if it doesn't parse. Clears history. */
+export function loadState(bytes: Uint8Array | ArrayLike<number>): boolean;
+/** Scene as a roadmap CanvasComposition; text layers and image urls are left empty for the app to fill. */
+export function exportComposition(): CanvasComposition | null;
+// Selection APIs: dragging any selected node moves the whole selection
+export function selectNodes(ids: Int32Array | number[]): void;
+export function deselectNodes(ids: Int32Array | number[]): void;
+export function toggleSelection(ids: Int32Array | number[]): void;
+export function clearSelection(): void;
+/** Selected node ids in ascending order. */
+export function getSelection(): Int32Array;
+// Z-order APIs: higher z is drawn and hit-tested on top
+export function bringToFront(id: number): boolean;
+export function sendToBack(id: number): boolean;
+/** Swap with the node directly above; false for unknown ids. */
+export function raiseNode(id: number): boolean;
+/** Swap with the node directly below; false for unknown ids. */
+export function lowerNode(id: number): boolean;
+export function setZIndex(id: number, z: number): boolean;
+/** Node ids from bottom to top. */
+export function getZOrder(): Int32Array;
 export function processFrame(input: { dt: number }): FrameOut;
+// Recording APIs. Captures every call that changes engine state (nodes, pointers, config, selection,
+// camera, animations, strokes, particles, images, history, loadState) and processFrame from the
+// current state. Undo history from before the start is not carried over.
+/** Start recording; false while a pointer is down or an animation, camera move or pending tap is in flight. */
+export function startRecording(): boolean;
+export function stopRecording(): Uint8Array;
+export function isRecording(): boolean;
+/** Reset the engine to the recording's starting state; false (engine untouched) if it doesn't parse. */
+export function loadRecording(bytes: Uint8Array | ArrayLike<number>): boolean;
+/** Replay inputs through the next recorded frame and return its output; null when the recording is used up. */
+export function replayFrame(): FrameOut | null;
 export function isReady(): boolean;
 export function storeImage(id: number, rgba: Uint8Array, w: number, h: number): boolean;
 export function resizeImage(id: number, outW: number, outH: number): Uint8Array;
@@ -28,6 +281,39 @@ export function spawnParticles(data: Float32Array | number[]): number;
 export function clearParticles(): void;
 export function setParticleParams(params: Float32Array | number[]): void;
 /**
- * Configure tap detection parameters: [tap_max_s, move_thresh_px, double_s, single_delay_s]
+ * Configure tap detection parameters:
+ * [tap_max_s, move_thresh_px, double_s, single_delay_s, long_press_s, max_taps]
+ * long_press_s (default 0.5, 0 = off) is how long a still press must last to fire long_press.
+ * max_taps (default 2) ends a tap sequence immediately; shorter sequences report after single_delay_s.
  */
 export function setTapParams(params: Float32Array | number[]): void;
+/**
+ * Configure throw inertia: [window_s, max_speed]. On release the node takes the
+ * pointer velocity averaged over the last `window_s` seconds (requires inertia > 0 in constraints).
+ */
+export function setThrowParams(params: Float32Array | number[]): void;
+/**
+ * Configure swipe detection: [minSpeed, minDistance] in world px/s and px (defaults 400, 30).
+ * A release that travelled minDistance from the press and was moving at least minSpeed
+ * emits swipe with the dominant direction of the release velocity; minSpeed 0 disables.
+ */
+export function setSwipeParams(params: Float32Array | number[]): void;
+/**
+ * Configure smart guides: [threshold, snapCenters]. While dragging, node edges
+ * (and centers) snap to other nodes within `threshold` world px; 0 disables.
+ * Once setViewport has a size, only nodes in view act as guides.
+ */
+export function setGuideParams(params: Float32Array | number[]): void;
+/**
+ * Configure resize handles: [handlePx, minW, minH, maxW, maxH]. Pressing within
+ * handlePx/2 screen px of a resizable node's edge or corner resizes it; edges snap
+ * to the constraint grid and stay inside bounds. maxW/maxH of 0 mean unbounded.
+ */
+export function setResizeParams(params: Float32Array | number[]): void;
+/**
+ * Configure a fixed timestep: [rateHz, maxSubsteps] (default off, 8). processFrame then
+ * simulates in whole 1/rateHz steps (at most maxSubsteps per frame; longer stalls are
+ * dropped) and transforms, sizes and locals are interpolated between the last two steps.
+ * rateHz 0 steps by dt.
+ */
+export function setTimestepParams(params: Float32Array | number[]): void;
@@ -7,7 +7,32 @@ let ready = false;
 let wasmInit = null;
 let wasmPkg = null;
 
+// Event schema this wrapper was written against (see EventKind in lib.rs)
+export const EVENT_SCHEMA_VERSION = 1;
+export const EventType = Object.freeze({
+  DRAG_START: 1,
+  DRAG_END: 2,
+  DRAG_MOVE: 3,
+  TAP: 10,
+  DOUBLE_TAP: 11,
+  LONG_PRESS: 12,
+  MULTI_TAP: 13,
+  SWIPE: 14,
+  PINCH_START: 20,
+  PINCH_END: 21,
+  COLLISION_START: 30,
+  COLLISION_END: 31,
+  RESIZE_START: 40,
+  RESIZE_END: 41,
+  DROP_ENTER: 50,
+  DROP_LEAVE: 51,
+  DROP: 52,
+  ANIM_END: 60
+});
+const EVENT_NAMES = Object.fromEntries(Object.entries(EventType).map(([k, v]) => [v, k.toLowerCase()]));
+
 export async function init(capacity = 256) {
+  let schemaError = null;
   try {
     // Try local pkg first (synced by scripts/sync-wasm.mjs)
     if (!wasmInit || !wasmPkg) {
@@ -27,10 +52,19 @@ export async function init(capacity = 256) {
     }
     mod = wasmPkg;
     if (mod && typeof mod.init === 'function') mod.init(capacity >>> 0);
-    ready = true;
+    const schema = eventSchema();
+    if (schema && schema.version !== EVENT_SCHEMA_VERSION) {
+      schemaError = new Error(`corlena: unsupported event schema v${schema.version} (expected v${EVENT_SCHEMA_VERSION})`);
+    }
+    ready = !schemaError;
   } catch (_) {
     ready = false;
   }
+  // A module this wrapper can't decode is not used at all
+  if (schemaError) {
+    mod = null;
+    throw schemaError;
+  }
 }
 
 export function reset() {
@@ -51,6 +85,61 @@ export function setViewParams(scale, panX, panY, pixelRatio) {
   }
 }
 
+export function setViewport(width, height) {
+  // CSS px, same units as pan; 0 = unknown (disables autoscroll)
+  if (mod && typeof mod.set_viewport === 'function') mod.set_viewport(Number(width) || 0, Number(height) || 0);
+}
+
+export function setAutoscrollParams(params) {
+  // [margin (CSS px, 0 = off), max_speed (CSS px/s at the edge)]
+  if (mod && typeof mod.set_autoscroll_params === 'function') {
+    const data = params instanceof Float32Array ? params : new Float32Array(params || []);
+    mod.set_autoscroll_params(data);
+  }
+}
+
+export function setCameraParams(params) {
+  // [min_zoom, max_zoom, pan_damping (fraction of fling velocity kept per second)]
+  if (mod && typeof mod.set_camera_params === 'function') {
+    const data = params instanceof Float32Array ? params : new Float32Array(params || []);
+    mod.set_camera_params(data);
+  }
+}
+
+export function setCameraBounds(l, t, r, b) {
+  if (mod && typeof mod.set_camera_bounds === 'function') mod.set_camera_bounds(Number(l), Number(t), Number(r), Number(b));
+}
+
+export function clearCameraBounds() {
+  if (mod && typeof mod.clear_camera_bounds === 'function') mod.clear_camera_bounds();
+}
+
+// Camera input: zoom anchored at a screen point (pointer coordinates), pan in CSS px
+export function zoomAt(factor, sx, sy) {
+  if (mod && typeof mod.zoom_at === 'function') mod.zoom_at(Number(factor) || 1, Number(sx) || 0, Number(sy) || 0);
+}
+
+export function panCamera(dx, dy) {
+  if (mod && typeof mod.pan_camera === 'function') mod.pan_camera(Number(dx) || 0, Number(dy) || 0);
+}
+
+export function releaseCameraPan() {
+  if (mod && typeof mod.release_camera_pan === 'function') mod.release_camera_pan();
+}
+
+export function zoomToRect(l, t, r, b, padding = 0, duration = 0) {
+  if (mod && typeof mod.zoom_to_rect === 'function') return !!mod.zoom_to_rect(Number(l), Number(t), Number(r), Number(b), Number(padding) || 0, Number(duration) || 0);
+  return false;
+}
+
+export function fitToNodes(ids, padding = 0, duration = 0) {
+  if (mod && typeof mod.fit_to_nodes === 'function') {
+    const data = ids instanceof Int32Array ? ids : new Int32Array(ids || []);
+    return !!mod.fit_to_nodes(data, Number(padding) || 0, Number(duration) || 0);
+  }
+  return false;
+}
+
 export function setConstraints(params) {
   if (mod && typeof mod.set_constraints === 'function') mod.set_constraints(params);
 }
@@ -59,23 +148,287 @@ export function upsertNodes(nodes) {
   if (mod && typeof mod.upsert_nodes === 'function') mod.upsert_nodes(nodes);
 }
 
+export function upsertNodesV2(nodes) {
+  // [id, x, y, w, h, vx, vy, flags, angle, scaleX, scaleY, pivotX, pivotY] * N
+  if (mod && typeof mod.upsert_nodes_v2 === 'function') mod.upsert_nodes_v2(nodes);
+}
+
+// Field groups for patchNodes, in record order: [id, ...values of each set group]
+export const PatchField = Object.freeze({
+  POSITION: 1,  // x, y
+  SIZE: 2,      // w, h
+  VELOCITY: 4,  // vx, vy
+  FLAGS: 8,     // flags
+  TRANSFORM: 16, // angle, scaleX, scaleY
+  PIVOT: 32     // pivotX, pivotY
+});
+
+export function patchNodes(fields, data) {
+  if (mod && typeof mod.patch_nodes === 'function') {
+    const buf = data instanceof Float32Array ? data : new Float32Array(data || []);
+    return mod.patch_nodes(fields >>> 0, buf) >>> 0;
+  }
+  return 0;
+}
+
+export function removeNodes(ids) {
+  if (mod && typeof mod.remove_nodes === 'function') return mod.remove_nodes(ids instanceof Int32Array ? ids : new Int32Array(ids || [])) >>> 0;
+  return 0;
+}
+
+export function clearNodes() {
+  if (mod && typeof mod.clear_nodes === 'function') mod.clear_nodes();
+}
+
 export function applyPointers(pointers) {
   if (mod && typeof mod.apply_pointers === 'function') mod.apply_pointers(pointers);
 }
 
+export function hitTest(x, y, screen = true) {
+  if (mod && typeof mod.hit_test === 'function') return mod.hit_test(Number(x), Number(y), !!screen) | 0;
+  return -1;
+}
+
+export function queryRect(l, t, r, b, screen = true) {
+  if (mod && typeof mod.query_rect === 'function') return mod.query_rect(Number(l), Number(t), Number(r), Number(b), !!screen);
+  return new Int32Array(0);
+}
+
+// Coordinate conversion with the engine's current view (identity before init)
+export function screenToWorld(x, y) {
+  if (mod && typeof mod.screen_to_world === 'function') {
+    const p = mod.screen_to_world(Number(x), Number(y));
+    return { x: p[0], y: p[1] };
+  }
+  return { x: Number(x), y: Number(y) };
+}
+
+export function worldToScreen(x, y) {
+  if (mod && typeof mod.world_to_screen === 'function') {
+    const p = mod.world_to_screen(Number(x), Number(y));
+    return { x: p[0], y: p[1] };
+  }
+  return { x: Number(x), y: Number(y) };
+}
+
+export function screenToWorldBatch(points) {
+  // [x, y] * N
+  const data = points instanceof Float32Array ? points : new Float32Array(points || []);
+  if (mod && typeof mod.screen_to_world_batch === 'function') return mod.screen_to_world_batch(data);
+  return data.slice(0, data.length & ~1);
+}
+
+export function worldToScreenBatch(points) {
+  const data = points instanceof Float32Array ? points : new Float32Array(points || []);
+  if (mod && typeof mod.world_to_screen_batch === 'function') return mod.world_to_screen_batch(data);
+  return data.slice(0, data.length & ~1);
+}
+
+// Hierarchy APIs (world position is preserved when attaching/detaching)
+export function setParent(child, parent) {
+  if (mod && typeof mod.set_parent === 'function') return !!mod.set_parent(child|0, parent == null ? -1 : parent|0);
+  return false;
+}
+
+export function groupNodes(parent, ids) {
+  if (mod && typeof mod.group_nodes === 'function') {
+    const data = ids instanceof Int32Array ? ids : new Int32Array(ids || []);
+    return mod.group_nodes(parent|0, data) >>> 0;
+  }
+  return 0;
+}
+
+export function ungroupNodes(parent) {
+  if (mod && typeof mod.ungroup_nodes === 'function') return mod.ungroup_nodes(parent|0) >>> 0;
+  return 0;
+}
+
+// Drop zone APIs: world rects that dragged nodes can hover and drop into
+export function upsertDropZones(zones) {
+  // [id, x, y, w, h, acceptMask, mode (0 = pointer, 1 = overlap)] * Z
+  if (mod && typeof mod.upsert_drop_zones === 'function') {
+    const data = zones instanceof Float32Array ? zones : new Float32Array(zones || []);
+    mod.upsert_drop_zones(data);
+  }
+}
+
+export function removeDropZone(id) {
+  if (mod && typeof mod.remove_drop_zone === 'function') return !!mod.remove_drop_zone(id|0);
+  return false;
+}
+
+export function clearDropZones() {
+  if (mod && typeof mod.clear_drop_zones === 'function') mod.clear_drop_zones();
+}
+
+// Animation APIs: the engine advances node animations in processFrame and emits anim_end
+export const AnimProp = Object.freeze({ X: 0, Y: 1, W: 2, H: 3, ANGLE: 4, SCALE: 5 });
+export const Easing = Object.freeze({ LINEAR: 0, EASE_IN_OUT: 1, EASE_OUT: 2, EASE_IN: 3 });
+
+export function animateNode(id, prop, target, duration, easing = Easing.EASE_IN_OUT) {
+  if (mod && typeof mod.animate_node === 'function') return !!mod.animate_node(id|0, prop >>> 0, Number(target), Number(duration) || 0, easing >>> 0);
+  return false;
+}
+
+export function springNode(id, prop, target, stiffness = 170, damping = 26) {
+  if (mod && typeof mod.spring_node === 'function') return !!mod.spring_node(id|0, prop >>> 0, Number(target), Number(stiffness), Number(damping));
+  return false;
+}
+
+export function cancelNodeAnimations(id) {
+  if (mod && typeof mod.cancel_node_animations === 'function') return mod.cancel_node_animations(id|0) >>> 0;
+  return 0;
+}
+
+// Selection APIs
+const toIds = (ids) => ids instanceof Int32Array ? ids : new Int32Array(ids || []);
+
+export function selectNodes(ids) {
+  if (mod && typeof mod.select_nodes === 'function') mod.select_nodes(toIds(ids));
+}
+
+export function deselectNodes(ids) {
+  if (mod && typeof mod.deselect_nodes === 'function') mod.deselect_nodes(toIds(ids));
+}
+
+export function toggleSelection(ids) {
+  if (mod && typeof mod.toggle_selection === 'function') mod.toggle_selection(toIds(ids));
+}
+
+export function clearSelection() {
+  if (mod && typeof mod.clear_selection === 'function') mod.clear_selection();
+}
+
+export function getSelection() {
+  if (mod && typeof mod.get_selection === 'function') return mod.get_selection();
+  return new Int32Array(0);
+}
+
+// Z-order APIs (stacking drives hit-testing and output order)
+export function bringToFront(id) {
+  if (mod && typeof mod.bring_to_front === 'function') return !!mod.bring_to_front(id|0);
+  return false;
+}
+
+export function sendToBack(id) {
+  if (mod && typeof mod.send_to_back === 'function') return !!mod.send_to_back(id|0);
+  return false;
+}
+
+export function raiseNode(id) {
+  if (mod && typeof mod.raise_node === 'function') return !!mod.raise_node(id|0);
+  return false;
+}
+
+export function lowerNode(id) {
+  if (mod && typeof mod.lower_node === 'function') return !!mod.lower_node(id|0);
+  return false;
+}
+
+export function setZIndex(id, z) {
+  if (mod && typeof mod.set_z_index === 'function') return !!mod.set_z_index(id|0, z|0);
+  return false;
+}
+
+export function getZOrder() {
+  if (mod && typeof mod.get_z_order === 'function') return mod.get_z_order();
+  return new Int32Array(0);
+}
+
 export function processFrame(input) {
   // Preferred: new API using internal state (dt only)
   if (mod && typeof mod.process_frame === 'function') {
     const out = mod.process_frame(Number(input?.dt ?? 0));
     return { 
       transforms: out.transforms, 
+      sizes: out.sizes || new Float32Array(0),
+      locals: out.locals || new Float32Array(0),
       particles: out.particles || new Float32Array(0), 
       drawPaths: out.drawPaths || new Float32Array(0),
-      events: out.events 
+      guides: out.guides || new Float32Array(0),
+      view: out.view || new Float32Array(0),
+      events: out.events,
+      eventData: out.eventData || new Float32Array(0)
     };
   }
   // Fallback noop
-  return { transforms: new Float32Array(0), particles: new Float32Array(0), drawPaths: new Float32Array(0), events: new Int32Array(0) };
+  return { transforms: new Float32Array(0), sizes: new Float32Array(0), locals: new Float32Array(0), particles: new Float32Array(0), drawPaths: new Float32Array(0), guides: new Float32Array(0), view: new Float32Array(0), events: new Int32Array(0), eventData: new Float32Array(0) };
+}
+
+// Input recording: every state-changing call and processFrame after startRecording, replayable frame by frame
+export function startRecording() {
+  if (mod && typeof mod.start_recording === 'function') return !!mod.start_recording();
+  return false;
+}
+
+export function stopRecording() {
+  if (mod && typeof mod.stop_recording === 'function') return mod.stop_recording();
+  return new Uint8Array(0);
+}
+
+export function isRecording() {
+  if (mod && typeof mod.is_recording === 'function') return !!mod.is_recording();
+  return false;
+}
+
+export function loadRecording(bytes) {
+  if (mod && typeof mod.load_recording === 'function') {
+    const arr = bytes instanceof Uint8Array ? bytes : new Uint8Array(bytes || []);
+    return !!mod.load_recording(arr);
+  }
+  return false;
+}
+
+export function replayFrame() {
+  if (mod && typeof mod.replay_frame === 'function') {
+    const out = mod.replay_frame();
+    if (!out) return null;
+    return {
+      transforms: out.transforms,
+      sizes: out.sizes || new Float32Array(0),
+      locals: out.locals || new Float32Array(0),
+      particles: out.particles || new Float32Array(0),
+      drawPaths: out.drawPaths || new Float32Array(0),
+      guides: out.guides || new Float32Array(0),
+      view: out.view || new Float32Array(0),
+      events: out.events,
+      eventData: out.eventData || new Float32Array(0)
+    };
+  }
+  return null;
+}
+
+// Layout description from the loaded module: { version, stride, header, types: [{ code, name, fields, payload }] }
+export function eventSchema() {
+  if (mod && typeof mod.event_schema === 'function') return mod.event_schema();
+  return null;
+}
+
+// Decode processFrame().events/eventData into objects. Throws when the header
+// names a schema version this wrapper does not understand.
+export function decodeEvents(events, eventData) {
+  const out = [];
+  if (!events || events.length < 4) return out;
+  let start = 0;
+  if (events[0] === 0) {
+    if (events[1] !== EVENT_SCHEMA_VERSION) {
+      throw new Error(`corlena: unsupported event schema v${events[1]} (expected v${EVENT_SCHEMA_VERSION})`);
+    }
+    start = 4;
+  }
+  for (let i = start; i + 3 < events.length; i += 4) {
+    const type = events[i];
+    const f = eventData && eventData.length === events.length ? eventData : null;
+    out.push({
+      type,
+      name: EVENT_NAMES[type] || 'unknown',
+      a: events[i + 1],
+      b: events[i + 2],
+      data: events[i + 3],
+      payload: f ? [f[i], f[i + 1], f[i + 2], f[i + 3]] : [0, 0, 0, 0]
+    });
+  }
+  return out;
 }
 
 export function isReady() {
@@ -121,13 +474,53 @@ export function setParticleParams(params) {
 }
 
 export function setTapParams(params) {
-  // [tap_max_s, move_thresh_px, double_s, single_delay_s]
+  // [tap_max_s, move_thresh_px, double_s, single_delay_s, long_press_s, max_taps]
   if (mod && typeof mod.set_tap_params === 'function') {
     const data = params instanceof Float32Array ? params : new Float32Array(params || []);
     mod.set_tap_params(data);
   }
 }
 
+export function setThrowParams(params) {
+  // [window_s, max_speed]
+  if (mod && typeof mod.set_throw_params === 'function') {
+    const data = params instanceof Float32Array ? params : new Float32Array(params || []);
+    mod.set_throw_params(data);
+  }
+}
+
+export function setSwipeParams(params) {
+  // [min_speed (world px/s, 0 = off), min_distance (world px)]
+  if (mod && typeof mod.set_swipe_params === 'function') {
+    const data = params instanceof Float32Array ? params : new Float32Array(params || []);
+    mod.set_swipe_params(data);
+  }
+}
+
+export function setGuideParams(params) {
+  // [threshold (world px, 0 = off), snap_centers (0/1)]
+  if (mod && typeof mod.set_guide_params === 'function') {
+    const data = params instanceof Float32Array ? params : new Float32Array(params || []);
+    mod.set_guide_params(data);
+  }
+}
+
+export function setResizeParams(params) {
+  // [handle_px (screen, 0 = off), min_w, min_h, max_w, max_h (0 = unbounded)]
+  if (mod && typeof mod.set_resize_params === 'function') {
+    const data = params instanceof Float32Array ? params : new Float32Array(params || []);
+    mod.set_resize_params(data);
+  }
+}
+
+export function setTimestepParams(params) {
+  // [rate_hz (0 = step by frame dt), max_substeps]
+  if (mod && typeof mod.set_timestep_params === 'function') {
+    const data = params instanceof Float32Array ? params : new Float32Array(params || []);
+    mod.set_timestep_params(data);
+  }
+}
+
 // Drawing APIs
 export function startDrawPath(id, x, y, pressure, color, width) {
   if (mod && typeof mod.start_draw_path === 'function') {
@@ -163,6 +556,69 @@ export function clearDrawPaths() {
   }
 }
 
+// History APIs: node gestures, strokes and node/path edits are recorded as undo steps
+export function undo() {
+  if (mod && typeof mod.undo === 'function') return !!mod.undo();
+  return false;
+}
+
+export function redo() {
+  if (mod && typeof mod.redo === 'function') return !!mod.redo();
+  return false;
+}
+
+export function canUndo() {
+  if (mod && typeof mod.can_undo === 'function') return !!mod.can_undo();
+  return false;
+}
+
+export function canRedo() {
+  if (mod && typeof mod.can_redo === 'function') return !!mod.can_redo();
+  return false;
+}
+
+export function beginHistoryGroup() {
+  if (mod && typeof mod.begin_history_group === 'function') mod.begin_history_group();
+}
+
+export function endHistoryGroup() {
+  if (mod && typeof mod.end_history_group === 'function') mod.end_history_group();
+}
+
+export function setHistoryDepth(depth) {
+  if (mod && typeof mod.set_history_depth === 'function') mod.set_history_depth(depth >>> 0);
+}
+
+export function pauseHistory(paused) {
+  if (mod && typeof mod.pause_history === 'function') mod.pause_history(!!paused);
+}
+
+export function clearHistory() {
+  if (mod && typeof mod.clear_history === 'function') mod.clear_history();
+}
+
+// Snapshots: versioned binary state for saving/restoring, JSON for interchange
+export function saveState() {
+  if (mod && typeof mod.save_state === 'function') return mod.save_state();
+  return new Uint8Array(0);
+}
+
+export function loadState(bytes) {
+  if (mod && typeof mod.load_state === 'function') {
+    const arr = bytes instanceof Uint8Array ? bytes : new Uint8Array(bytes || []);
+    return !!mod.load_state(arr);
+  }
+  return false;
+}
+
+export function exportComposition() {
+  if (mod && typeof mod.export_composition === 'function') {
+    const json = mod.export_composition();
+    return json ? JSON.parse(json) : null;
+  }
+  return null;
+}
+
 export function getDrawPathsCount() {
   if (mod && typeof mod.get_draw_paths_count === 'function') {
     return mod.get_draw_paths_count() >>> 0;
@@ -1,19 +1,115 @@
 use wasm_bindgen::prelude::*;
 use js_sys::{Float32Array, Int32Array, Object, Reflect, Uint8Array};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
 // Typed-array layout (MVP):
 // nodes: [id, x, y, w, h, vx, vy, flags] * N
-// pointers: [id, x, y, pressure, buttons] * P  (pressure optional; if omitted, stride=4)
+// nodes v2: [id, x, y, w, h, vx, vy, flags, angle, scaleX, scaleY, pivotX, pivotY] * N
+//   pivot is a fraction of w/h (0.5, 0.5 = center); v1 upserts keep the node's current transform
+// pointers: [pointerId, x, y, pressure, buttons] * P  (pressure optional; if omitted, stride=4)
+//   x/y are screen px; a press hit-tests nodes in world space and captures the topmost one
+//   world = (screen / pixelRatio - pan) / scale  (exported as screen_to_world / world_to_screen)
 // constraints: [left, top, right, bottom, gridX, gridY, inertia, damping]
-// transforms out: [id, x, y, angle, scaleX, scaleY, reserved] * N
-// events out (ring): [type, a, b, data] * E
-//   type codes:
-//     1 = drag_start (a=nodeId)
-//     2 = drag_end   (a=nodeId)
-//    10 = tap        (a=nodeId, b=1)
-//    11 = double_tap (a=nodeId, b=2)
+// drop zones: [id, x, y, w, h, acceptMask, mode] * Z  (world rects; later zones win overlaps)
+//   accept: node flags & mask != 0 (0 = any node); mode 0 = pointer inside, 1 = node box overlaps
+// transforms out: [id, x, y, angle, scaleX, scaleY, reserved] * N  (bottom-to-top z order)
+//   angle in radians about the node pivot; scale = view scale * node scale
+// locals out: [id, parentId, x, y, angle, scaleX, scaleY] * N
+//   transform relative to the parent's unrotated, unscaled box (parentId -1 = root, same as world)
+// sizes out: [id, w, h] * N  (same order as transforms; unscaled node size)
+// view out: [scale, panX, panY, pixelRatio]  (world -> CSS px is matrix(scale, 0, 0, scale, panX, panY))
+// guides out: [axis, position, nodeId, otherNodeId] * G
+//   axis 0 = vertical line at world x, 1 = horizontal line at world y; active while dragging
+// events out (ring): [0, schemaVersion, 4, count] header, then [type, a, b, data] * E
+// event data out: [f0, f1, f2, f3] * (E + 1)  (float payload of the same-index record)
+//   type codes, fields and payloads: see EventKind (also exported as event_schema())
+//   resize handles: 0=nw, 1=n, 2=ne, 3=e, 4=se, 5=s, 6=sw, 7=w
+// node flags:
+//   bit 0 = no_hit  (ignored by pointer hit-testing)
+//   bit 1 = collide (AABB separation against other collide nodes)
+//   bit 2 = pinned  (never moved by collisions; blocks dragged nodes)
+//   bit 3 = resizable   (a press on an edge/corner handle resizes instead of dragging)
+//   bit 4 = lock_aspect (resizing keeps the width/height ratio)
+// node patches: [id, ...values of each set field group] * N  (see PATCH_FIELDS)
+//   only the chosen fields change; drag, tap and animation state is kept
+
+const FLAG_NO_HIT: u32 = 1 << 0;
+const FLAG_COLLIDE: u32 = 1 << 1;
+const FLAG_PINNED: u32 = 1 << 2;
+const FLAG_RESIZABLE: u32 = 1 << 3;
+const FLAG_LOCK_ASPECT: u32 = 1 << 4;
+
+// patch_nodes field groups and their value counts, in record order.
+const PATCH_POSITION: u32 = 1 << 0;  // x, y
+const PATCH_SIZE: u32 = 1 << 1;      // w, h
+const PATCH_VELOCITY: u32 = 1 << 2;  // vx, vy
+const PATCH_FLAGS: u32 = 1 << 3;     // flags
+const PATCH_TRANSFORM: u32 = 1 << 4; // angle, scaleX, scaleY
+const PATCH_PIVOT: u32 = 1 << 5;     // pivotX, pivotY
+const PATCH_FIELDS: [(u32, usize); 6] = [
+    (PATCH_POSITION, 2), (PATCH_SIZE, 2), (PATCH_VELOCITY, 2), (PATCH_FLAGS, 1), (PATCH_TRANSFORM, 3), (PATCH_PIVOT, 2),
+];
+
+// Bumped whenever an event code, field or payload changes meaning.
+const EVENT_SCHEMA_VERSION: i32 = 1;
+
+// Event type codes; discriminants are part of the schema and never reused.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EventKind {
+    DragStart = 1,
+    DragEnd = 2,
+    DragMove = 3,
+    Tap = 10,
+    DoubleTap = 11,
+    LongPress = 12,
+    MultiTap = 13,
+    Swipe = 14,
+    PinchStart = 20,
+    PinchEnd = 21,
+    CollisionStart = 30,
+    CollisionEnd = 31,
+    ResizeStart = 40,
+    ResizeEnd = 41,
+    DropEnter = 50,
+    DropLeave = 51,
+    Drop = 52,
+    AnimEnd = 60,
+}
+
+// Animatable node properties; the discriminants are the `prop` codes of animate_node/spring_node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AnimProp {
+    X = 0,
+    Y = 1,
+    W = 2,
+    H = 3,
+    Angle = 4,
+    Scale = 5, // uniform: drives scale_x and scale_y together
+}
+
+// Springs still moving after this long (seconds) jump to their target.
+const SPRING_MAX_S: f32 = 10.0;
+
+// Running node animation: eased over `duration`, or a spring when `spring` is set.
+#[derive(Clone, Copy, Debug)]
+struct Tween {
+    node: i32,
+    prop: AnimProp,
+    from: f32,
+    to: f32,
+    value: f32,
+    t: f32,
+    duration: f32,
+    easing: u32,
+    // (stiffness, damping) per unit mass; `vel` is the spring's current speed
+    spring: Option<(f32, f32)>,
+    vel: f32,
+}
+
+// Edge directions per resize handle, clockwise from the top-left corner.
+const HANDLES: [(i32, i32); 8] = [(-1, -1), (0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0)];
 
 #[derive(Clone, Debug)]
 struct Node {
@@ -25,20 +121,306 @@ struct Node {
     vx: f32,
     vy: f32,
     flags: u32,
+    // Per-node transform (radians, multipliers) about the pivot (fraction of w/h)
+    angle: f32,
+    scale_x: f32,
+    scale_y: f32,
+    pivot_x: f32,
+    pivot_y: f32,
+    // Stacking: higher z is drawn (and hit) above; ties keep insertion order
+    z: i32,
+    // Hierarchy: world fields above follow parent * local unless the node moved itself
+    parent: Option<i32>,
+    local: Local,
+    local_dirty: bool,
     // Drag state
     grabbing: bool,
+    grab_pointer: i32, // pointer id driving the drag
     grab_dx: f32,
     grab_dy: f32,
-    // Tap detection state
-    down_time: f32,
-    down_x: f32,
-    down_y: f32,
-    max_move: f32,
+    // Set on selected nodes carried along by another node's drag
+    group_leader: Option<i32>,
+    // Two-pointer gesture state
+    pinch: Option<Pinch>,
+    // Handle drag in progress
+    resize: Option<Resize>,
+    // Drop zone the dragged node is currently over
+    drop_zone: Option<i32>,
+    // Position at the last drag_start/drag_move event
+    drag_x: f32,
+    drag_y: f32,
+    // Tap detection state; tap_count taps so far in the current sequence, last at tap_x/y
     last_tap_time: f32,
+    tap_count: u32,
+    tap_x: f32,
+    tap_y: f32,
     single_pending: bool,
     single_emit_time: f32,
 }
 
+// Transform relative to the parent: top-left in parent box space, angle/scale relative.
+#[derive(Clone, Copy, Debug)]
+struct Local {
+    x: f32,
+    y: f32,
+    angle: f32,
+    scale_x: f32,
+    scale_y: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Pinch {
+    a: i32, // pointer ids
+    b: i32,
+    // Pointer span and direction at gesture start
+    dist0: f32,
+    angle0: f32,
+    // Node transform at gesture start
+    node_angle0: f32,
+    scale_x0: f32,
+    scale_y0: f32,
+    // Centroid in unrotated, unscaled node space (relative to pivot)
+    lx: f32,
+    ly: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Resize {
+    pointer: i32,
+    handle: usize, // index into HANDLES
+    // Node box at gesture start
+    x0: f32,
+    y0: f32,
+    w0: f32,
+    h0: f32,
+    // Moving edges minus the pointer, in node space
+    dx: f32,
+    dy: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct DropZone {
+    id: i32,
+    // World rect
+    l: f32,
+    t: f32,
+    r: f32,
+    b: f32,
+    accept: u32,      // node flag mask; 0 accepts every node
+    by_pointer: bool, // hit by the pointer position instead of node box overlap
+}
+
+#[derive(Clone, Debug)]
+struct Pointer {
+    node: Option<i32>, // captured node id
+    // Last known world position, and the raw screen position it came from
+    x: f32,
+    y: f32,
+    sx: f32,
+    sy: f32,
+    // Press state (world space, seconds)
+    down_x: f32,
+    down_y: f32,
+    down_time: f32,
+    max_move: f32,
+    long_pressed: bool,
+    // Recent samples for release velocity
+    samples: Samples,
+}
+
+// Recent (time, x, y) samples of a moving position.
+#[derive(Clone, Debug, Default)]
+struct Samples(Vec<(f32, f32, f32)>);
+
+// Animated camera move between view centers (world) and scales.
+#[derive(Clone, Copy, Debug)]
+struct CameraAnim {
+    cx0: f32,
+    cy0: f32,
+    s0: f32,
+    cx1: f32,
+    cy1: f32,
+    s1: f32,
+    t: f32,
+    duration: f32,
+}
+
+// Snapshot format (save_state/load_state): b"CRLN", u32 version, then little-endian sections:
+//   view [scale, panX, panY, pixelRatio], constraints [8 x f32], particle params [gX, gY, damping, restitution]
+//   nodes:      u32 count, [id i32, x, y, w, h, vx, vy, flags u32, angle, scaleX, scaleY, pivotX, pivotY, z i32, parent i32 (-1 = root)] * N
+//   draw paths: u32 count, [id i32, color u32, width, closed u8, points u32, [x, y, pressure, t] * points] * P  (ascending id)
+//   particles:  u32 count, [x, y, vx, vy, r, life] * Q
+//   images:     u32 count, [id i32, w u32, h u32, rgba u8 * w * h * 4] * I  (ascending id)
+const STATE_MAGIC: &[u8; 4] = b"CRLN";
+const STATE_VERSION: u32 = 1;
+
+#[derive(Default)]
+struct StateWriter(Vec<u8>);
+
+struct StateReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+// Input recording (start_recording/stop_recording): b"CRLR", u32 version, then little-endian:
+//   preamble: u32 count, [kind u32, n u32, f32 * n] * K  (config not covered by the snapshot)
+//   snapshot: u32 length, save_state bytes
+//   inputs:   [kind u32, frame u32, n u32, f32 * n] until the end, frame = process_frame calls before it
+const RECORDING_MAGIC: &[u8; 4] = b"CRLR";
+const RECORDING_VERSION: u32 = 1;
+
+// Recorded engine inputs; args are the export's arguments as f32s. Integer arguments (ids, z,
+// bit fields, colors) are stored bit-for-bit (`int_arg`), byte buffers as a length then 4 bytes
+// per value (`byte_args`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+enum Input {
+    Frame = 0,            // [dt]
+    Upsert = 1,           // upsert_nodes data
+    UpsertV2 = 2,         // upsert_nodes_v2 data
+    Pointers = 3,         // apply_pointers data
+    Patch = 4,            // [fields, ...patch_nodes data]
+    Remove = 5,           // [...ids]
+    ClearNodes = 6,
+    Reset = 7,
+    View = 10,            // [scale]
+    ViewParams = 11,      // [scale, panX, panY, pixelRatio]
+    Viewport = 12,        // [width, height]
+    AutoscrollParams = 13,
+    CameraParams = 14,
+    CameraBounds = 15,    // [left, top, right, bottom]; an inverted rect clears
+    Constraints = 16,
+    TapParams = 17,
+    ThrowParams = 18,
+    SwipeParams = 19,
+    GuideParams = 20,
+    ResizeParams = 21,
+    ParticleParams = 22,
+    TimestepParams = 23,
+    DropZones = 30,       // upsert_drop_zones data
+    RemoveDropZone = 31,  // [id]
+    ClearDropZones = 32,
+    Clock = 40,           // [time, accumulator]; preamble only
+    SetParent = 50,       // [child, parent]
+    Group = 51,           // [parent, ...ids]
+    Ungroup = 52,         // [parent]
+    BringToFront = 53,    // [id]
+    SendToBack = 54,      // [id]
+    Raise = 55,           // [id]
+    Lower = 56,           // [id]
+    SetZ = 57,            // [id, z]
+    Select = 60,          // [...ids]; in the preamble, applied after the snapshot
+    Deselect = 61,        // [...ids]
+    ToggleSelection = 62, // [...ids]
+    ClearSelection = 63,
+    ZoomAt = 70,          // [factor, sx, sy]
+    Pan = 71,             // [dx, dy]
+    ReleasePan = 72,
+    ZoomToRect = 73,      // [l, t, r, b, padding, duration]
+    FitToNodes = 74,      // [padding, duration, ...ids]
+    Animate = 80,         // [id, prop, target, duration, easing]
+    Spring = 81,          // [id, prop, target, stiffness, damping]
+    CancelAnimations = 82, // [id]
+    StartPath = 90,       // [id, x, y, pressure, color, width]
+    AddPoint = 91,        // [id, x, y, pressure]
+    FinishPath = 92,      // [id, closed]
+    RemovePath = 93,      // [id]
+    ClearPaths = 94,
+    SpawnParticles = 100, // spawn_particles data
+    ClearParticles = 101,
+    SeedParticles = 102,  // [n]
+    StoreImage = 103,     // [id, w, h, ...rgba bytes]
+    Undo = 110,
+    Redo = 111,
+    BeginGroup = 112,
+    EndGroup = 113,
+    HistoryDepth = 114,   // [depth]
+    PauseHistory = 115,   // [paused]
+    ClearHistory = 116,
+    LoadState = 120,      // [...load_state bytes]
+}
+
+struct Recorder {
+    out: StateWriter,
+    frame: u32,
+}
+
+// Decoded snapshot, applied only once the whole buffer has parsed.
+struct SavedState {
+    view: [f32; 4],
+    constraints: [f32; 8],
+    particle_params: [f32; 4],
+    nodes: Vec<(i32, NodeState, f32, f32)>, // id, state, vx, vy
+    paths: Vec<DrawPath>,
+    particles: Vec<Particle>,
+    images: Vec<(i32, Image)>,
+}
+
+// Persistent node fields kept by undo/redo; gesture and velocity state is not recorded.
+#[derive(Clone, Debug, PartialEq)]
+struct NodeState {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    flags: u32,
+    angle: f32,
+    scale_x: f32,
+    scale_y: f32,
+    pivot_x: f32,
+    pivot_y: f32,
+    z: i32,
+    parent: Option<i32>,
+}
+
+// One undo step: (before, after) per changed node or draw path; None = absent.
+#[derive(Clone, Debug)]
+enum Change {
+    Node(i32, Option<NodeState>, Option<NodeState>),
+    Path(i32, Option<DrawPath>, Option<DrawPath>),
+}
+
+// What a node's output is drawn from: world [x, y, angle, scaleX, scaleY], size [w, h], and
+// local (to its parent) [x, y, angle, scaleX, scaleY].
+type Pose = [f32; 12];
+
+// Pre-edit state of each node / draw path touched by the open change; None = absent.
+type Touched = (BTreeMap<i32, Option<NodeState>>, BTreeMap<i32, Option<DrawPath>>);
+
+// Undo/redo stacks. While any change is open, `base` collects what it touches; closing the
+// outermost one diffs just those ids against the current state and pushes a single entry.
+#[derive(Default)]
+struct History {
+    undo: Vec<Vec<Change>>,
+    redo: Vec<Vec<Change>>,
+    depth: usize,
+    open: u32,
+    base: Option<Touched>,
+    // edits are applied but not recorded (layout sync from the bindings)
+    paused: bool,
+    // a pointer gesture on nodes is in progress / strokes started but not finished
+    gesture: bool,
+    strokes: BTreeSet<i32>,
+}
+
+// Uniform grid over world space; node ids are bucketed by every cell their AABB touches.
+#[derive(Default)]
+struct SpatialGrid {
+    cell: f32,
+    cells: HashMap<(i32, i32), Vec<i32>>,
+    // Cell span per node; None = too large to bucket, kept in `large` instead
+    spans: HashMap<i32, Option<(i32, i32, i32, i32)>>,
+    large: Vec<i32>,
+}
+
+// Event records drained each frame: ints [type, a, b, data] with a float payload
+// [f0, f1, f2, f3] per record, kept index-aligned.
+#[derive(Default)]
+struct Events {
+    ints: Vec<i32>,
+    floats: Vec<f32>,
+}
+
 #[derive(Clone, Debug)]
 struct Image {
     w: u32,
@@ -56,7 +438,7 @@ struct Particle {
     life: f32,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 struct DrawPath {
     id: i32,
     points: Vec<f32>, // [x, y, pressure, timestamp] * N
@@ -69,10 +451,44 @@ struct DrawPath {
 struct Engine {
     nodes: Vec<Node>,
     index: HashMap<i32, usize>,
+    // active pointers keyed by pointer id
+    pointers: HashMap<i32, Pointer>,
+    // z assigned to the next inserted node (keeps insertion order by default)
+    next_z: i32,
+    // selected node ids; dragging any of them moves all
+    selection: BTreeSet<i32>,
+    // spatial index over node AABBs
+    grid: SpatialGrid,
+    // collide-flag pairs currently touching, keyed (min id, max id)
+    contacts: HashSet<(i32, i32)>,
+    // drop targets, in registration order
+    drop_zones: Vec<DropZone>,
+    // node animations, in start order (at most one per node and property)
+    tweens: Vec<Tween>,
+    // undo/redo of node and draw path edits
+    history: History,
+    // smart guides: config (world px) and lines active this frame
+    guide_threshold: f32,
+    guide_centers: bool,
+    guides: Vec<f32>,
     scale: f32,
     pan_x: f32,
     pan_y: f32,
     pixel_ratio: f32,
+    // viewport size (CSS px, 0 = unknown) and edge autoscroll while dragging
+    view_w: f32,
+    view_h: f32,
+    autoscroll_margin: f32,
+    autoscroll_speed: f32,
+    // camera: zoom limits, content bounds (world, clamps pan), pan inertia (CSS px/s) and animation
+    min_zoom: f32,
+    max_zoom: f32,
+    content: Option<(f32, f32, f32, f32)>,
+    cam_vx: f32,
+    cam_vy: f32,
+    cam_damping: f32,
+    cam_samples: Samples,
+    cam_anim: Option<CameraAnim>,
     // constraints/state
     left: f32,
     top: f32,
@@ -93,24 +509,55 @@ struct Engine {
     // drawing paths
     draw_paths: HashMap<i32, DrawPath>,
     // events ring buffer (drain each frame)
-    events: Vec<i32>,
+    events: Events,
     // time accumulator (seconds)
     time: f32,
+    // fixed timestep (seconds, 0 = step by frame dt): unsimulated time, substep cap, and
+    // (id, before, after) poses of the last step for interpolated output, indexed like `nodes`
+    fixed_dt: f32,
+    max_substeps: u32,
+    accumulator: f32,
+    lerp_poses: Vec<(i32, Pose, Pose)>,
     // tap config (seconds, pixels)
     tap_max_s: f32,
     move_thresh_px: f32,
     double_s: f32,
     single_delay_s: f32,
+    long_press_s: f32,
+    max_taps: u32,
+    // throw config (seconds, world px/s)
+    throw_window_s: f32,
+    max_throw_speed: f32,
+    // swipe config (world px/s, world px; min speed 0 = off)
+    swipe_min_speed: f32,
+    swipe_min_dist: f32,
+    // resize config: handle size (screen px, 0 = off), size limits (max 0 = unbounded)
+    handle_px: f32,
+    min_w: f32,
+    min_h: f32,
+    max_w: f32,
+    max_h: f32,
+    // input recording in progress, and recorded inputs left to replay
+    recorder: Option<Recorder>,
+    replay: VecDeque<(Input, Vec<f32>)>,
 }
 
 impl Engine {
     fn new(capacity: usize) -> Self {
         let mut e = Engine::default();
         e.nodes.reserve(capacity);
+        e.grid.cell = 256.0;
+        e.guide_threshold = 0.0; // smart guides off
+        e.guide_centers = true;
         e.scale = 1.0;
         e.pan_x = 0.0;
         e.pan_y = 0.0;
         e.pixel_ratio = 1.0;
+        e.autoscroll_margin = 48.0;  // CSS px from the viewport edge (0 = off)
+        e.autoscroll_speed = 1200.0; // pan speed at the very edge (CSS px/s)
+        e.min_zoom = 0.1;
+        e.max_zoom = 10.0;
+        e.cam_damping = 0.05;        // fraction of pan velocity kept after one second
         e.left = 0.0;
         e.top = 0.0;
         e.right = f32::INFINITY;
@@ -126,21 +573,45 @@ impl Engine {
         e.g_y = 600.0; // gravity y (px/s^2)
         e.p_damping = 0.999; // per frame exp factor (applied with powf(dt))
         e.restitution = 0.6; // bounce factor
-        e.events = Vec::new();
+        e.events = Events::default();
         // time + tap defaults
         e.time = 0.0;
         e.tap_max_s = 0.28;       // max press duration for single tap
         e.move_thresh_px = 6.0;   // max movement (in world px)
         e.double_s = 0.28;        // max gap between taps for double
         e.single_delay_s = 0.25;  // delay before emitting single, to allow double
+        e.long_press_s = 0.5;     // hold time for long press (0 = off)
+        e.max_taps = 2;           // taps in a sequence before it completes
+        e.throw_window_s = 0.1;   // pointer history used for release velocity
+        e.max_throw_speed = 4000.0;
+        e.swipe_min_speed = 400.0;
+        e.swipe_min_dist = 30.0;
+        e.handle_px = 12.0;
+        e.min_w = 20.0;
+        e.min_h = 20.0;
+        e.max_w = 0.0;
+        e.max_h = 0.0;
+        e.history.depth = 100;
+        e.max_substeps = 8;       // fixed steps per frame before falling behind real time
         e
     }
 
     fn reset(&mut self) {
         self.nodes.clear();
         self.index.clear();
+        self.next_z = 0;
+        self.pointers.clear();
+        self.selection.clear();
+        self.grid.clear();
+        self.contacts.clear();
+        self.drop_zones.clear();
+        self.tweens.clear();
+        self.history = History { depth: self.history.depth, ..History::default() };
+        self.guides.clear();
         self.scale = 1.0;
         self.pan_x = 0.0; self.pan_y = 0.0; self.pixel_ratio = 1.0;
+        self.content = None;
+        self.stop_camera();
         self.left = 0.0; self.top = 0.0;
         self.right = f32::INFINITY; self.bottom = f32::INFINITY;
         self.grid_x = 1.0; self.grid_y = 1.0;
@@ -150,31 +621,40 @@ impl Engine {
         self.draw_paths.clear();
         self.events.clear();
         self.time = 0.0;
+        self.accumulator = 0.0;
+        self.lerp_poses.clear();
     }
 
-    fn upsert_nodes(&mut self, data: &[f32]) {
-        // chunk size = 8 f32s per node (id is f32 in the buffer; cast to i32)
-        let stride = 8;
-        if data.len() % stride != 0 { return; }
+    fn upsert_nodes(&mut self, data: &[f32], stride: usize) {
+        // chunk size = 8 (v1) or 13 (v2) f32s per node (id is f32 in the buffer; cast to i32)
+        if stride != 8 && stride != 13 { return; }
+        if !data.len().is_multiple_of(stride) { return; }
         for chunk in data.chunks(stride) {
             let id = chunk[0] as i32;
-            let n = Node {
-                id,
-                x: chunk[1], y: chunk[2],
-                w: chunk[3], h: chunk[4],
-                vx: chunk[5], vy: chunk[6],
-                flags: chunk[7] as u32,
-                grabbing: false,
-                grab_dx: 0.0,
-                grab_dy: 0.0,
-                down_time: 0.0,
-                down_x: 0.0,
-                down_y: 0.0,
-                max_move: 0.0,
-                last_tap_time: -1000.0,
-                single_pending: false,
-                single_emit_time: 0.0,
+            self.touch(id);
+            // An existing node keeps its gesture, tap, stacking and hierarchy state (and, for
+            // v1, its transform) so a re-upsert mid-drag doesn't drop the gesture
+            let mut n = match self.index.get(&id) {
+                Some(&idx) => self.nodes[idx].clone(),
+                None => {
+                    if self.next_z == i32::MAX { self.rerank(); }
+                    self.next_z += 1;
+                    Node { z: self.next_z - 1, ..Node::new(id) }
+                }
             };
+            if stride == 13 {
+                n.angle = chunk[8];
+                n.scale_x = if chunk[9] > 0.0 { chunk[9] } else { 1.0 };
+                n.scale_y = if chunk[10] > 0.0 { chunk[10] } else { 1.0 };
+                n.pivot_x = chunk[11];
+                n.pivot_y = chunk[12];
+            }
+            (n.x, n.y, n.w, n.h) = (chunk[1], chunk[2], chunk[3], chunk[4]);
+            (n.vx, n.vy) = (chunk[5], chunk[6]);
+            n.flags = chunk[7] as u32;
+            n.local = Local { x: n.x, y: n.y, angle: n.angle, scale_x: n.scale_x, scale_y: n.scale_y };
+            n.local_dirty = true;
+            self.grid.insert(id, n.aabb());
             if let Some(&idx) = self.index.get(&id) {
                 self.nodes[idx] = n;
             } else {
@@ -183,80 +663,610 @@ impl Engine {
                 self.index.insert(id, idx);
             }
         }
+        self.sync_hierarchy();
+    }
+
+    // Update chosen fields of existing nodes, keeping the rest; returns nodes patched.
+    fn patch_nodes(&mut self, fields: u32, data: &[f32]) -> u32 {
+        let stride = 1 + PATCH_FIELDS.iter().filter(|(f, _)| fields & f != 0).map(|(_, w)| w).sum::<usize>();
+        if stride == 1 || !data.len().is_multiple_of(stride) { return 0; }
+        let mut count = 0;
+        for chunk in data.chunks(stride) {
+            let Some(&idx) = self.index.get(&(chunk[0] as i32)) else { continue; };
+            let n = &mut self.nodes[idx];
+            self.history.touch_node(n.id, Some(n));
+            let mut values = chunk[1..].iter().copied();
+            let mut next = || values.next().unwrap_or(0.0);
+            if fields & PATCH_POSITION != 0 {
+                let (x, y) = (next(), next());
+                // A held node stays at its new offset from the pointer; the jump is not a drag move
+                n.grab_dx += x - n.x; n.grab_dy += y - n.y;
+                n.drag_x += x - n.x; n.drag_y += y - n.y;
+                n.x = x; n.y = y;
+            }
+            if fields & PATCH_SIZE != 0 { n.w = next().max(0.0); n.h = next().max(0.0); }
+            if fields & PATCH_VELOCITY != 0 { n.vx = next(); n.vy = next(); }
+            if fields & PATCH_FLAGS != 0 { n.flags = next() as u32; }
+            if fields & PATCH_TRANSFORM != 0 {
+                n.angle = next();
+                let (sx, sy) = (next(), next());
+                n.scale_x = if sx > 0.0 { sx } else { 1.0 };
+                n.scale_y = if sy > 0.0 { sy } else { 1.0 };
+            }
+            if fields & PATCH_PIVOT != 0 { n.pivot_x = next(); n.pivot_y = next(); }
+            n.local_dirty = true;
+            self.grid.insert(n.id, n.aabb());
+            count += 1;
+        }
+        self.sync_hierarchy();
+        count
+    }
+
+    fn remove_nodes(&mut self, ids: &[i32]) -> u32 {
+        ids.iter().filter(|&&id| self.remove_node(id)).count() as u32
+    }
+
+    fn clear_nodes(&mut self) {
+        let ids: Vec<i32> = self.nodes.iter().map(|n| n.id).collect();
+        self.remove_nodes(&ids);
     }
 
     fn apply_pointers(&mut self, data: &[f32]) {
-        // Support stride 5 (with pressure) or 4 (without): [id, x, y, pressure?, buttons]
-        let stride = if data.len() % 5 == 0 { 5 } else { 4 };
-        if data.len() % stride != 0 { return; }
+        // Support stride 5 (with pressure) or 4 (without): [pointerId, x, y, pressure?, buttons]
+        let stride = if data.len().is_multiple_of(5) { 5 } else { 4 };
+        if !data.len().is_multiple_of(stride) { return; }
         for chunk in data.chunks(stride) {
-            let id = chunk[0] as i32;
+            let pid = chunk[0] as i32;
             // Convert incoming pointer coords from screen to world using current view params
-            let s = if self.scale > 0.0 { self.scale } else { 1.0 };
-            let pr = if self.pixel_ratio > 0.0 { self.pixel_ratio } else { 1.0 };
-            let sx = chunk[1];
-            let sy = chunk[2];
-            let x = (sx / pr - self.pan_x) / s;
-            let y = (sy / pr - self.pan_y) / s;
+            let (x, y) = self.screen_to_world(chunk[1], chunk[2]);
             let buttons = if stride == 5 { chunk[4] } else { chunk[3] };
-            if let Some(&idx) = self.index.get(&id) {
+            if buttons > 0.0 {
+                if !self.pointers.contains_key(&pid) { self.pointer_down(pid, x, y); }
+                if let Some(p) = self.pointers.get_mut(&pid) { p.sx = chunk[1]; p.sy = chunk[2]; }
+                self.pointer_move(pid, x, y);
+            } else if self.pointers.contains_key(&pid) {
+                self.release_pointer(pid, x, y);
+            }
+        }
+        self.sync_hierarchy();
+    }
+
+    fn screen_to_world(&self, sx: f32, sy: f32) -> (f32, f32) {
+        let s = if self.scale > 0.0 { self.scale } else { 1.0 };
+        let pr = if self.pixel_ratio > 0.0 { self.pixel_ratio } else { 1.0 };
+        ((sx / pr - self.pan_x) / s, (sy / pr - self.pan_y) / s)
+    }
+
+    fn world_to_screen(&self, x: f32, y: f32) -> (f32, f32) {
+        let s = if self.scale > 0.0 { self.scale } else { 1.0 };
+        let pr = if self.pixel_ratio > 0.0 { self.pixel_ratio } else { 1.0 };
+        ((x * s + self.pan_x) * pr, (y * s + self.pan_y) * pr)
+    }
+
+    // Convert [x, y] pairs between screen (pointer) and world space; a trailing odd value is dropped.
+    fn map_points(&self, pts: &[f32], to_world: bool) -> Vec<f32> {
+        let mut out = Vec::with_capacity(pts.len() & !1);
+        for c in pts.chunks_exact(2) {
+            let (x, y) = if to_world { self.screen_to_world(c[0], c[1]) } else { self.world_to_screen(c[0], c[1]) };
+            out.extend_from_slice(&[x, y]);
+        }
+        out
+    }
+
+    // Node indices bottom to top.
+    fn stacking(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.nodes.len()).collect();
+        order.sort_by_key(|&i| (self.nodes[i].z, i));
+        order
+    }
+
+    fn bring_to_front(&mut self, id: i32) -> bool {
+        let Some(&idx) = self.index.get(&id) else { return false; };
+        if self.stacking().last() == Some(&idx) { return true; }
+        if self.nodes.iter().any(|n| n.z == i32::MAX) { self.rerank(); }
+        let top = self.nodes.iter().map(|n| n.z).max().unwrap_or(0);
+        self.set_z(idx, top + 1);
+        true
+    }
+
+    fn send_to_back(&mut self, id: i32) -> bool {
+        let Some(&idx) = self.index.get(&id) else { return false; };
+        if self.stacking().first() == Some(&idx) { return true; }
+        if self.nodes.iter().any(|n| n.z == i32::MIN) { self.rerank(); }
+        let bottom = self.nodes.iter().map(|n| n.z).min().unwrap_or(0);
+        self.set_z(idx, bottom - 1);
+        true
+    }
+
+    // Renumber z to 0..N in stacking order; for when explicit values leave no room.
+    fn rerank(&mut self) {
+        let order = self.stacking();
+        for (rank, &i) in order.iter().enumerate() {
+            let n = &mut self.nodes[i];
+            self.history.touch_node(n.id, Some(n));
+            n.z = rank as i32;
+        }
+        self.next_z = order.len() as i32;
+    }
+
+    fn set_z(&mut self, idx: usize, z: i32) {
+        let n = &mut self.nodes[idx];
+        self.history.touch_node(n.id, Some(n));
+        n.z = z;
+        // New nodes always land on top
+        self.next_z = self.next_z.max(z.saturating_add(1));
+    }
+
+    // Explicit z for a node by id, as one undo step.
+    fn set_z_index(&mut self, id: i32, z: i32) -> bool {
+        let Some(&idx) = self.index.get(&id) else { return false; };
+        self.record(|eng| eng.set_z(idx, z));
+        true
+    }
+
+    // Swap places with the neighbour one step up (+1) or down (-1) the stack by trading z values,
+    // so explicit z (and the gaps between them) survive; only a tie with the neighbour re-ranks.
+    fn shift_z(&mut self, id: i32, dir: i32) -> bool {
+        let Some(&idx) = self.index.get(&id) else { return false; };
+        let order = self.stacking();
+        let pos = order.iter().position(|&i| i == idx).unwrap_or(0);
+        let Some(&other) = pos.checked_add_signed(dir as isize).and_then(|p| order.get(p)) else { return true; };
+        if self.nodes[other].z == self.nodes[idx].z { self.rerank(); }
+        let (a, b) = (self.nodes[idx].z, self.nodes[other].z);
+        self.set_z(idx, b);
+        self.set_z(other, a);
+        true
+    }
+
+    // Topmost node containing the world point (highest z wins).
+    fn hit_test_world(&self, x: f32, y: f32) -> Option<i32> {
+        let mut ids = Vec::new();
+        self.grid.query(x, y, x, y, &mut ids);
+        ids.iter()
+            .filter_map(|id| self.index.get(id).copied())
+            .filter(|&idx| {
+                let n = &self.nodes[idx];
+                n.flags & FLAG_NO_HIT == 0 && n.contains(x, y)
+            })
+            .max_by_key(|&idx| (self.nodes[idx].z, idx))
+            .map(|idx| self.nodes[idx].id)
+    }
+
+    // Ids of nodes whose bounding box overlaps the world rect, in stacking order.
+    fn query_rect_world(&self, l: f32, t: f32, r: f32, b: f32) -> Vec<i32> {
+        let (l, r) = (l.min(r), l.max(r));
+        let (t, b) = (t.min(b), t.max(b));
+        let mut ids = Vec::new();
+        self.grid.query(l, t, r, b, &mut ids);
+        let mut hits: Vec<usize> = ids.iter()
+            .filter_map(|id| self.index.get(id).copied())
+            .filter(|&idx| {
+                let (nl, nt, nr, nb) = self.nodes[idx].aabb();
+                nl <= r && nr >= l && nt <= b && nb >= t
+            })
+            .collect();
+        hits.sort_unstable_by_key(|&idx| (self.nodes[idx].z, idx));
+        hits.iter().map(|&idx| self.nodes[idx].id).collect()
+    }
+
+    // Resize handle under the world point, unless another node's body covers it.
+    fn handle_hit(&self, x: f32, y: f32) -> Option<(i32, usize)> {
+        // handle_px is screen px like pointer input, so it shrinks in world space with zoom and pixel ratio
+        let reach = self.handle_px * 0.5 / (self.scale.max(1e-4) * self.pixel_ratio.max(1e-4));
+        if reach <= 0.0 { return None; }
+        let mut ids = Vec::new();
+        self.grid.query(x - reach, y - reach, x + reach, y + reach, &mut ids);
+        let (idx, handle) = ids.iter()
+            .filter_map(|id| self.index.get(id).copied())
+            .filter_map(|idx| {
+                let n = &self.nodes[idx];
+                if n.flags & FLAG_NO_HIT != 0 { return None; }
+                let handle = if n.flags & FLAG_RESIZABLE != 0 { n.handle_at(x, y, reach) } else { None };
+                (handle.is_some() || n.contains(x, y)).then_some((idx, handle))
+            })
+            .max_by_key(|&(idx, _)| (self.nodes[idx].z, idx))?;
+        Some((self.nodes[idx].id, handle?))
+    }
+
+    fn pointer_down(&mut self, pid: i32, x: f32, y: f32) {
+        let handle = self.handle_hit(x, y);
+        let node = match handle {
+            Some((id, _)) => Some(id),
+            None => self.hit_test_world(x, y),
+        };
+        let samples = Samples(vec![(self.time, x, y)]);
+        // Touching the canvas catches a flung or animating camera
+        self.cam_anim = None;
+        self.cam_vx = 0.0; self.cam_vy = 0.0;
+        let (sx, sy) = self.world_to_screen(x, y);
+        // A new press starts its own step even if the last throw is still moving
+        self.end_gesture(true);
+        self.pointers.insert(pid, Pointer { node, x, y, sx, sy, down_x: x, down_y: y, down_time: self.time, max_move: 0.0, long_pressed: false, samples });
+        let Some(id) = node else { return; };
+        // Everything until the last node pointer lifts and the released nodes come to rest
+        // (throws coasted out, grid snapped) is one undo step
+        if !self.history.gesture {
+            self.history.gesture = true;
+            self.begin_change();
+        }
+        let Some(&idx) = self.index.get(&id) else { return; };
+        // A grab takes over from any running animation
+        self.cancel_tweens(id);
+        let n = &mut self.nodes[idx];
+        // Extra pointers on a node being resized are ignored
+        if n.resize.is_some() { return; }
+        if let (Some((_, h)), false) = (handle, n.grabbing) {
+            let (ex, ey) = HANDLES[h];
+            let (lx, ly) = n.to_local(x, y);
+            n.resize = Some(Resize {
+                pointer: pid,
+                handle: h,
+                x0: n.x, y0: n.y, w0: n.w, h0: n.h,
+                dx: if ex < 0 { -lx } else if ex > 0 { n.w - lx } else { 0.0 },
+                dy: if ey < 0 { -ly } else if ey > 0 { n.h - ly } else { 0.0 },
+            });
+            n.vx = 0.0; n.vy = 0.0;
+            // Event: resize_start(nodeId, handle)
+            self.events.push_with(EventKind::ResizeStart, [id, h as i32, 0], [n.x, n.y, n.w, n.h]);
+            return;
+        }
+        // On press-start capture the offset between pointer and node top-left
+        if !n.grabbing {
+            n.grabbing = true;
+            n.grab_pointer = pid;
+            n.grab_dx = n.x - x;
+            n.grab_dy = n.y - y;
+            n.drag_x = n.x;
+            n.drag_y = n.y;
+            // Event: drag_start(nodeId)
+            self.events.push_with(EventKind::DragStart, [id, 0, 0], [n.x, n.y, x, y]);
+            if self.selection.contains(&id) { self.start_group(id); }
+        } else if n.pinch.is_none() && n.group_leader.is_none() {
+            // Second pointer on a held node starts a pinch/rotate
+            let Some(a) = self.pointers.get(&n.grab_pointer) else { return; };
+            let (dx, dy) = (x - a.x, y - a.y);
+            let (cx, cy) = ((a.x + x) * 0.5, (a.y + y) * 0.5);
+            let (lx, ly) = n.to_local(cx, cy);
+            n.pinch = Some(Pinch {
+                a: n.grab_pointer,
+                b: pid,
+                dist0: (dx*dx + dy*dy).sqrt(),
+                angle0: dy.atan2(dx),
+                node_angle0: n.angle,
+                scale_x0: n.scale_x,
+                scale_y0: n.scale_y,
+                lx: lx - n.pivot_x * n.w,
+                ly: ly - n.pivot_y * n.h,
+            });
+            // Event: pinch_start(nodeId, pointerA, pointerB)
+            self.events.push(EventKind::PinchStart, [id, n.grab_pointer, pid]);
+        }
+    }
+
+    fn pointer_move(&mut self, pid: i32, x: f32, y: f32) {
+        let Some(p) = self.pointers.get_mut(&pid) else { return; };
+        p.x = x;
+        p.y = y;
+        p.samples.record(self.time, x, y, self.throw_window_s);
+        // track movement since press
+        let dx = x - p.down_x;
+        let dy = y - p.down_y;
+        let d = (dx*dx + dy*dy).sqrt();
+        if d > p.max_move { p.max_move = d; }
+        self.drive_pointer(pid, x, y);
+    }
+
+    // Move whatever the pointer holds (drag, resize or pinch) to its world position.
+    fn drive_pointer(&mut self, pid: i32, x: f32, y: f32) {
+        let Some(id) = self.pointers.get(&pid).and_then(|p| p.node) else { return; };
+        let Some(&idx) = self.index.get(&id) else { return; };
+        if let Some(r) = self.nodes[idx].resize {
+            if r.pointer == pid { self.apply_resize(idx, r, x, y); }
+            return;
+        }
+        if let Some(g) = self.nodes[idx].pinch {
+            if g.a == pid || g.b == pid { self.apply_pinch(idx, g); }
+            return;
+        }
+        let n = &self.nodes[idx];
+        if n.grabbing && n.grab_pointer == pid {
+            let (dx, dy) = (x + n.grab_dx - n.x, y + n.grab_dy - n.y);
+            self.shift_group(idx, dx, dy);
+        }
+    }
+
+    // Carry the other selected, idle nodes along with the node being grabbed.
+    fn start_group(&mut self, leader: i32) {
+        for &id in &self.selection {
+            if id == leader { continue; }
+            let Some(&idx) = self.index.get(&id) else { continue; };
+            let n = &mut self.nodes[idx];
+            if n.grabbing { continue; }
+            n.grabbing = true;
+            n.grab_pointer = -1;
+            n.group_leader = Some(leader);
+            n.vx = 0.0; n.vy = 0.0;
+        }
+        let carried: Vec<i32> = self.group_members(leader).into_iter().map(|i| self.nodes[i].id).collect();
+        for id in carried { self.cancel_tweens(id); }
+    }
+
+    fn group_members(&self, leader: i32) -> Vec<usize> {
+        (0..self.nodes.len()).filter(|&i| self.nodes[i].group_leader == Some(leader)).collect()
+    }
+
+    // Translate a grabbed node and everything it carries by the same delta.
+    fn shift_group(&mut self, idx: usize, dx: f32, dy: f32) {
+        let mut members = self.group_members(self.nodes[idx].id);
+        members.push(idx);
+        for i in members {
+            let n = &mut self.nodes[i];
+            self.history.touch_node(n.id, Some(n));
+            n.x += dx;
+            n.y += dy;
+            n.vx = 0.0; n.vy = 0.0;
+            self.grid.insert(n.id, n.aabb());
+        }
+    }
+
+    // Union box of a grabbed node and everything it carries.
+    fn group_aabb(&self, idx: usize) -> (f32, f32, f32, f32) {
+        let (mut l, mut t, mut r, mut b) = self.nodes[idx].aabb();
+        for i in self.group_members(self.nodes[idx].id) {
+            let (ml, mt, mr, mb) = self.nodes[i].aabb();
+            l = l.min(ml); t = t.min(mt); r = r.max(mr); b = b.max(mb);
+        }
+        (l, t, r, b)
+    }
+
+    // Keep a dragged group's union box inside bounds so members keep their offsets.
+    fn clamp_groups(&mut self) {
+        for idx in 0..self.nodes.len() {
+            let n = &self.nodes[idx];
+            if !n.grabbing || n.group_leader.is_some() { continue; }
+            if self.group_members(n.id).is_empty() { continue; }
+            let (dx, dy) = clamp_offset(self.group_aabb(idx), self.left, self.top, self.right, self.bottom);
+            if dx != 0.0 || dy != 0.0 { self.shift_group(idx, dx, dy); }
+        }
+    }
+
+    fn select(&mut self, ids: &[i32]) {
+        for id in ids {
+            if self.index.contains_key(id) { self.selection.insert(*id); }
+        }
+    }
+
+    fn deselect(&mut self, ids: &[i32]) {
+        for id in ids { self.selection.remove(id); }
+    }
+
+    fn toggle_selection(&mut self, ids: &[i32]) {
+        for id in ids {
+            if !self.selection.remove(id) && self.index.contains_key(id) { self.selection.insert(*id); }
+        }
+    }
+
+    // Move the handle's edges to the pointer in the node's starting frame, then
+    // apply grid snapping (axis-aligned nodes), aspect lock, size limits and bounds.
+    fn apply_resize(&mut self, idx: usize, r: Resize, x: f32, y: f32) {
+        let mut f = self.nodes[idx].clone();
+        f.x = r.x0; f.y = r.y0; f.w = r.w0; f.h = r.h0;
+        let lock = f.flags & FLAG_LOCK_ASPECT != 0;
+        let axis_aligned = f.angle == 0.0;
+        let (ex, ey) = HANDLES[r.handle];
+        let (mut lx, mut ly) = f.to_local(x, y);
+        lx += r.dx;
+        ly += r.dy;
+        if axis_aligned {
+            let (mut wx, mut wy) = f.to_world(lx, ly);
+            if self.grid_x > 1.0 { wx = (wx / self.grid_x).round() * self.grid_x; }
+            if self.grid_y > 1.0 { wy = (wy / self.grid_y).round() * self.grid_y; }
+            (lx, ly) = f.to_local(wx, wy);
+        }
+        let mut w = match ex { -1 => r.w0 - lx, 1 => lx, _ => r.w0 };
+        let mut h = match ey { -1 => r.h0 - ly, 1 => ly, _ => r.h0 };
+        // Under aspect lock a side handle also grows the other axis right/down
+        let gx = if ex == 0 && lock { 1 } else { ex };
+        let gy = if ey == 0 && lock { 1 } else { ey };
+        let mut max_w = if self.max_w > 0.0 { self.max_w } else { f32::INFINITY };
+        let mut max_h = if self.max_h > 0.0 { self.max_h } else { f32::INFINITY };
+        if axis_aligned {
+            let (bl, bt) = f.to_local(self.left, self.top);
+            let (br, bb) = f.to_local(self.right, self.bottom);
+            if gx < 0 { max_w = max_w.min(r.w0 - bl); } else if gx > 0 { max_w = max_w.min(br); }
+            if gy < 0 { max_h = max_h.min(r.h0 - bt); } else if gy > 0 { max_h = max_h.min(bb); }
+        }
+        if lock {
+            // Width drives unless only a horizontal edge moves
+            let k = if ex != 0 { w / r.w0.max(1e-4) } else { h / r.h0.max(1e-4) };
+            let k = k
+                .min(max_w / r.w0.max(1e-4)).min(max_h / r.h0.max(1e-4))
+                .max(self.min_w / r.w0.max(1e-4)).max(self.min_h / r.h0.max(1e-4));
+            w = r.w0 * k;
+            h = r.h0 * k;
+        } else {
+            w = w.min(max_w).max(self.min_w);
+            h = h.min(max_h).max(self.min_h);
+        }
+        // Keep the opposite edges where they were
+        let l = if gx < 0 { r.w0 - w } else { 0.0 };
+        let t = if gy < 0 { r.h0 - h } else { 0.0 };
+        let n = &mut self.nodes[idx];
+        self.history.touch_node(n.id, Some(n));
+        let (px, py) = f.to_world(l + n.pivot_x * w, t + n.pivot_y * h);
+        n.w = w;
+        n.h = h;
+        n.x = px - n.pivot_x * w;
+        n.y = py - n.pivot_y * h;
+        n.local_dirty = true;
+        self.grid.insert(n.id, n.aabb());
+    }
+
+    // Scale and rotate by the change in pointer span, keeping the node point
+    // that was under the starting centroid under the current centroid.
+    fn apply_pinch(&mut self, idx: usize, g: Pinch) {
+        let (Some(a), Some(b)) = (self.pointers.get(&g.a), self.pointers.get(&g.b)) else { return; };
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        let (cx, cy) = ((a.x + b.x) * 0.5, (a.y + b.y) * 0.5);
+        let ratio = if g.dist0 > 0.0 { (dx*dx + dy*dy).sqrt() / g.dist0 } else { 1.0 };
+        let n = &mut self.nodes[idx];
+        self.history.touch_node(n.id, Some(n));
+        n.scale_x = (g.scale_x0 * ratio).max(1e-4);
+        n.scale_y = (g.scale_y0 * ratio).max(1e-4);
+        n.angle = g.node_angle0 + (dy.atan2(dx) - g.angle0);
+        let (ox, oy) = rotate(g.lx * n.scale_x, g.ly * n.scale_y, n.angle);
+        n.x = cx - ox - n.pivot_x * n.w;
+        n.y = cy - oy - n.pivot_y * n.h;
+        n.vx = 0.0; n.vy = 0.0;
+        self.grid.insert(n.id, n.aabb());
+    }
+
+    fn release_pointer(&mut self, pid: i32, x: f32, y: f32) {
+        let Some(mut p) = self.pointers.remove(&pid) else { return; };
+        p.x = x;
+        p.y = y;
+        p.samples.record(self.time, x, y, self.throw_window_s);
+        let Some(id) = p.node else { self.detect_swipe(&p, -1); return; };
+        let Some(&idx) = self.index.get(&id) else { return; };
+        if let Some(r) = self.nodes[idx].resize {
+            if r.pointer == pid {
                 let n = &mut self.nodes[idx];
-                if buttons > 0.0 {
-                    // On press-start capture the offset between pointer and node top-left
-                    if !n.grabbing {
-                        n.grabbing = true;
-                        n.grab_dx = n.x - x;
-                        n.grab_dy = n.y - y;
-                        // Tap start
-                        n.down_time = self.time;
-                        n.down_x = x;
-                        n.down_y = y;
-                        n.max_move = 0.0;
-                        // Event: drag_start(nodeId)
-                        self.events.extend_from_slice(&[1, id as i32, 0, 0]);
-                    }
-                    // track movement since press
-                    let dx = x - n.down_x;
-                    let dy = y - n.down_y;
-                    let d = (dx*dx + dy*dy).sqrt();
-                    if d > n.max_move { n.max_move = d; }
-                    n.x = x + n.grab_dx;
-                    n.y = y + n.grab_dy;
-                    n.vx = 0.0; n.vy = 0.0;
-                } else if n.grabbing {
-                    // Release
-                    n.grabbing = false;
-                    // Event: drag_end(nodeId)
-                    self.events.extend_from_slice(&[2, id as i32, 0, 0]);
-                    // Determine tap vs drag based on duration and move threshold
-                    let press_dur = (self.time - n.down_time).max(0.0);
-                    let is_tap = press_dur <= self.tap_max_s && n.max_move <= self.move_thresh_px;
-                    if is_tap {
-                        let since_last = self.time - n.last_tap_time;
-                        if since_last >= 0.0 && since_last <= self.double_s {
-                            // Double tap: cancel pending single if any
-                            if n.single_pending { n.single_pending = false; }
-                            self.events.extend_from_slice(&[11, id as i32, 2, 0]);
-                            n.last_tap_time = -1000.0;
-                        } else {
-                            // Schedule single tap after delay
-                            n.single_pending = true;
-                            n.single_emit_time = self.time + self.single_delay_s;
-                            n.last_tap_time = self.time;
-                        }
-                    }
-                }
+                n.resize = None;
+                // Event: resize_end(nodeId, handle)
+                self.events.push_with(EventKind::ResizeEnd, [id, r.handle as i32, 0], [n.x, n.y, n.w, n.h]);
+            }
+            return;
+        }
+        // Another pointer still holding the node takes over the drag
+        let next = self.pointers.iter()
+            .filter(|(_, q)| q.node == Some(id))
+            .map(|(&qid, q)| (qid, q.x, q.y))
+            .min_by_key(|&(qid, _, _)| qid);
+        let n = &mut self.nodes[idx];
+        if !n.grabbing { return; }
+        let mut reanchor = n.grab_pointer == pid;
+        if n.pinch.is_some_and(|g| g.a == pid || g.b == pid) {
+            n.pinch = None;
+            // Node moved under both pointers; the survivor needs a fresh offset
+            reanchor = true;
+            // Event: pinch_end(nodeId)
+            self.events.push(EventKind::PinchEnd, [id, 0, 0]);
+        }
+        if let Some((qid, qx, qy)) = next {
+            if reanchor {
+                n.grab_pointer = qid;
+                n.grab_dx = n.x - qx;
+                n.grab_dy = n.y - qy;
+            }
+            return;
+        }
+        // Only the driving pointer ends the drag (not one resting on a carried node)
+        if n.grab_pointer != pid { return; }
+        // Release; hand the recent pointer velocity to the node as a throw
+        n.grabbing = false;
+        n.grab_pointer = -1;
+        self.drop_at(idx, x, y);
+        let n = &mut self.nodes[idx];
+        let (mut vx, mut vy) = p.samples.velocity(self.time, self.throw_window_s);
+        let speed = (vx*vx + vy*vy).sqrt();
+        let k = if speed > self.max_throw_speed { self.max_throw_speed / speed } else { 1.0 };
+        vx *= k;
+        vy *= k;
+        // Without inertia the release velocity is only reported
+        let (tx, ty) = if self.inertia > 0.0 { (vx, vy) } else { (0.0, 0.0) };
+        if self.inertia > 0.0 { n.vx = tx; n.vy = ty; }
+        let (nx, ny) = (n.x, n.y);
+        for i in self.group_members(id) {
+            let m = &mut self.nodes[i];
+            m.grabbing = false;
+            m.group_leader = None;
+            m.vx = tx; m.vy = ty;
+        }
+        // Event: drag_end(nodeId)
+        self.events.push_with(EventKind::DragEnd, [id, 0, 0], [nx, ny, vx, vy]);
+        self.detect_swipe(&p, id);
+        let n = &mut self.nodes[idx];
+        // Determine tap vs drag based on duration and move threshold
+        let press_dur = (self.time - p.down_time).max(0.0);
+        let is_tap = press_dur <= self.tap_max_s && p.max_move <= self.move_thresh_px && !p.long_pressed;
+        if is_tap {
+            let since_last = self.time - n.last_tap_time;
+            n.tap_count = if since_last >= 0.0 && since_last <= self.double_s { n.tap_count + 1 } else { 1 };
+            n.tap_x = x;
+            n.tap_y = y;
+            if n.tap_count >= self.max_taps {
+                // Sequence complete: emit now and cancel the pending shorter one
+                n.single_pending = false;
+                let (kind, args) = tap_event(id, n.tap_count);
+                self.events.push_with(kind, args, [x, y, 0.0, 0.0]);
+                n.tap_count = 0;
+                n.last_tap_time = -1000.0;
+            } else {
+                // Schedule after delay so a further tap can extend the sequence
+                n.single_pending = true;
+                n.single_emit_time = self.time + self.single_delay_s;
+                n.last_tap_time = self.time;
+            }
+        }
+    }
+
+    // One frame of dt: a single step, or with a fixed timestep as many whole steps as have built up
+    // (at most max_substeps; time beyond that is dropped rather than caught up on).
+    fn advance(&mut self, dt: f32) {
+        if self.fixed_dt <= 0.0 {
+            self.step(dt);
+            return;
+        }
+        self.accumulator += dt.max(0.0);
+        let mut steps = 0;
+        while self.accumulator >= self.fixed_dt && steps < self.max_substeps {
+            if steps + 1 == self.max_substeps || self.accumulator < self.fixed_dt * 2.0 {
+                self.lerp_poses = self.nodes.iter().map(|n| (n.id, n.pose(), n.pose())).collect();
+            }
+            self.step(self.fixed_dt);
+            self.accumulator -= self.fixed_dt;
+            steps += 1;
+        }
+        if steps == 0 { return; }
+        self.accumulator %= self.fixed_dt;
+        for ((id, _, after), n) in self.lerp_poses.iter_mut().zip(&self.nodes) {
+            if *id == n.id { *after = n.pose(); }
+        }
+    }
+
+    // Pose drawn this frame. With a fixed timestep nodes are blended between the last two steps by
+    // the leftover time, unless something else moved them since (drags, upserts and patches show as-is)
+    // or nodes were added or removed since, leaving `lerp_poses` out of line with `nodes`.
+    fn output_pose(&self, idx: usize) -> Pose {
+        let n = &self.nodes[idx];
+        let pose = n.pose();
+        match self.lerp_poses.get(idx) {
+            Some((id, before, after)) if *id == n.id && self.fixed_dt > 0.0 && *after == pose => {
+                let k = (self.accumulator / self.fixed_dt).clamp(0.0, 1.0);
+                std::array::from_fn(|i| before[i] + (after[i] - before[i]) * k)
             }
+            _ => pose,
         }
     }
 
     fn step(&mut self, dt: f32) {
         // advance time
         self.time += dt.max(0.0);
+        self.step_camera(dt);
+        self.autoscroll(dt);
+        self.advance_tweens(dt);
+        self.apply_guides();
+        self.clamp_groups();
+        // Grid snapping waits until a node's animations finish
+        let animated: HashSet<i32> = self.tweens.iter().map(|t| t.node).collect();
         // Integrate velocities with optional inertia + damping
         let use_inertia = self.inertia > 0.0;
         let damp = if self.damping < 1.0 { self.damping.powf(dt.max(0.0)) } else { 1.0 };
+        if use_inertia { self.glide_children(dt, damp); }
         for n in &mut self.nodes {
+            // Children are placed by their parent unless dragged themselves
+            if n.parent.is_some() && !n.grabbing { continue; }
+            let before = self.history.before(n);
             if use_inertia {
                 n.x += n.vx * dt;
                 n.y += n.vy * dt;
@@ -267,21 +1277,41 @@ impl Engine {
                 }
             }
             // Grid snapping (steps <= 1 mean no-op), but skip while actively grabbing to prevent jump
-            if !n.grabbing {
+            if !n.grabbing && n.resize.is_none() && !animated.contains(&n.id) {
                 if self.grid_x > 1.0 { n.x = (n.x / self.grid_x).round() * self.grid_x; }
                 if self.grid_y > 1.0 { n.y = (n.y / self.grid_y).round() * self.grid_y; }
             }
-            // Clamp to bounds
-            let max_x = if self.right.is_finite() { (self.right - self.left - n.w).max(0.0) } else { f32::INFINITY };
-            let max_y = if self.bottom.is_finite() { (self.bottom - self.top - n.h).max(0.0) } else { f32::INFINITY };
-            n.x = n.x.max(self.left).min(self.left + max_x);
-            n.y = n.y.max(self.top).min(self.top + max_y);
+            n.clamp_to(self.left, self.top, self.right, self.bottom);
+            self.history.touch_changed(n, before);
+            self.grid.insert(n.id, n.aabb());
+        }
+
+        if self.nodes.iter().any(|n| n.flags & FLAG_COLLIDE != 0) {
+            self.resolve_collisions();
+        } else if !self.contacts.is_empty() {
+            self.update_contacts(HashSet::new());
+        }
+        self.sync_hierarchy();
+
+        // Report where each dragged node ended up this frame (carried nodes ride along)
+        for n in &mut self.nodes {
+            if !n.grabbing || n.group_leader.is_some() { continue; }
+            let (dx, dy) = (n.x - n.drag_x, n.y - n.drag_y);
+            if dx == 0.0 && dy == 0.0 { continue; }
+            n.drag_x = n.x;
+            n.drag_y = n.y;
+            // Event: drag_move(nodeId, pointerId)
+            self.events.push_with(EventKind::DragMove, [n.id, n.grab_pointer, 0], [n.x, n.y, dx, dy]);
         }
+        self.update_drop_targets();
+        self.end_gesture(false);
 
-        // Emit any scheduled single taps now that enough time has elapsed
+        self.detect_long_presses();
+        // Emit any scheduled taps now that enough time has elapsed
         for n in &mut self.nodes {
             if n.single_pending && self.time >= n.single_emit_time {
-                self.events.extend_from_slice(&[10, n.id as i32, 1, 0]);
+                let (kind, args) = tap_event(n.id, n.tap_count);
+                self.events.push_with(kind, args, [n.tap_x, n.tap_y, 0.0, 0.0]);
                 n.single_pending = false;
             }
         }
@@ -315,213 +1345,2617 @@ impl Engine {
         }
     }
 
-    fn write_transforms(&self) -> Float32Array {
-        // [id, x, y, angle, scaleX, scaleY, reserved]
-        let stride = 7usize;
-        let mut out: Vec<f32> = Vec::with_capacity(self.nodes.len() * stride);
-        for n in &self.nodes {
-            out.push(n.id as f32);
-            out.push(n.x);
-            out.push(n.y);
-            out.push(0.0); // angle
-            out.push(self.scale.max(0.0001)); // scaleX
-            out.push(self.scale.max(0.0001)); // scaleY
-            out.push(0.0); // reserved
+    // A thrown child glides in its parent's frame, so it keeps riding along with the parent.
+    fn glide_children(&mut self, dt: f32, damp: f32) {
+        for i in 0..self.nodes.len() {
+            let n = &self.nodes[i];
+            if n.parent.is_none() || n.grabbing || (n.vx == 0.0 && n.vy == 0.0) { continue; }
+            let Some(p) = n.parent.and_then(|pid| self.index.get(&pid)).map(|&pi| &self.nodes[pi]) else {
+                // A child of a missing parent is never placed; drop its throw
+                self.nodes[i].vx = 0.0; self.nodes[i].vy = 0.0;
+                continue;
+            };
+            let (dx, dy) = rotate(n.vx * dt, n.vy * dt, -p.angle);
+            let (dx, dy) = (dx / p.scale_x.max(1e-4), dy / p.scale_y.max(1e-4));
+            let n = &mut self.nodes[i];
+            self.history.touch_node(n.id, Some(n));
+            n.local.x += dx;
+            n.local.y += dy;
+            if self.damping < 1.0 {
+                n.vx *= damp; n.vy *= damp;
+                if n.vx.abs() < 1e-3 { n.vx = 0.0; }
+                if n.vy.abs() < 1e-3 { n.vy = 0.0; }
+            }
         }
-        let arr = Float32Array::new_with_length(out.len() as u32);
-        arr.copy_from(&out[..]);
-        arr
     }
 
-    fn write_particles(&self) -> Float32Array {
-        // [x, y, vx, vy, r, life] * N
-        let stride = 6usize;
-        let mut out: Vec<f32> = Vec::with_capacity(self.particles.len() * stride);
-        for p in &self.particles {
-            out.push(p.x);
-            out.push(p.y);
-            out.push(p.vx);
-            out.push(p.vy);
-            out.push(p.r);
-            out.push(p.life);
+    // Pan while a dragging or resizing pointer sits within the margin of a viewport edge,
+    // faster the deeper it is. Held pointers keep their screen position, so their world
+    // position is re-derived and the captured nodes follow the finger.
+    fn autoscroll(&mut self, dt: f32) {
+        if dt <= 0.0 || self.autoscroll_margin <= 0.0 || self.view_w <= 0.0 || self.view_h <= 0.0 { return; }
+        let pr = if self.pixel_ratio > 0.0 { self.pixel_ratio } else { 1.0 };
+        let m = self.autoscroll_margin.min(self.view_w * 0.5).min(self.view_h * 0.5);
+        // +1 at (or past) the low edge, -1 at the high edge, linear ramp across the margin
+        let edge = |v: f32, len: f32| {
+            if v < m { (m - v).min(m) / m } else if v > len - m { -(v - (len - m)).min(m) / m } else { 0.0 }
+        };
+        let mut pids: Vec<i32> = self.pointers.keys().copied().collect();
+        pids.sort_unstable();
+        let mut pan = None;
+        for &pid in &pids {
+            let p = &self.pointers[&pid];
+            let Some(&idx) = p.node.and_then(|id| self.index.get(&id)) else { continue; };
+            let n = &self.nodes[idx];
+            let dragging = n.grabbing && n.grab_pointer == pid && n.pinch.is_none();
+            let resizing = n.resize.is_some_and(|r| r.pointer == pid);
+            if !dragging && !resizing { continue; }
+            let (fx, fy) = (edge(p.sx / pr, self.view_w), edge(p.sy / pr, self.view_h));
+            if fx != 0.0 || fy != 0.0 { pan = Some((fx, fy)); break; }
         }
-        let arr = Float32Array::new_with_length(out.len() as u32);
-        arr.copy_from(&out[..]);
-        arr
+        let Some((fx, fy)) = pan else { return; };
+        self.pan_x += fx * self.autoscroll_speed * dt;
+        self.pan_y += fy * self.autoscroll_speed * dt;
+        self.clamp_camera();
+        self.follow_pointers();
     }
 
-    fn write_draw_paths(&self) -> Float32Array {
-        // Format: [path_id, color, width, closed, point_count, x1, y1, pressure1, timestamp1, x2, y2, ...] per path
-        let mut out: Vec<f32> = Vec::new();
-        for path in self.draw_paths.values() {
-            out.push(path.id as f32);
-            out.push(path.color as f32);
-            out.push(path.width);
-            out.push(if path.closed { 1.0 } else { 0.0 });
-            out.push((path.points.len() / 4) as f32); // point count
-            out.extend_from_slice(&path.points);
+    // After the camera moves, held pointers stay put on screen: re-derive their world positions
+    // so whatever they drag keeps up with the finger.
+    fn follow_pointers(&mut self) {
+        let mut pids: Vec<i32> = self.pointers.keys().copied().collect();
+        pids.sort_unstable();
+        for pid in pids {
+            let Some(p) = self.pointers.get(&pid) else { continue; };
+            let (x, y) = self.screen_to_world(p.sx, p.sy);
+            let (dx, dy) = (x - p.x, y - p.y);
+            // The camera moved, not the finger: carry the press point and samples along
+            // so this isn't counted as travel (tap, long press) or throw velocity
+            let Some(p) = self.pointers.get_mut(&pid) else { continue; };
+            p.x = x;
+            p.y = y;
+            p.down_x += dx;
+            p.down_y += dy;
+            p.samples.shift(dx, dy);
+            self.drive_pointer(pid, x, y);
         }
-        let arr = Float32Array::new_with_length(out.len() as u32);
-        arr.copy_from(&out[..]);
-        arr
     }
-}
 
-thread_local! {
-    static ENGINE: RefCell<Option<Engine>> = RefCell::new(None);
-}
+    fn stop_camera(&mut self) {
+        self.cam_anim = None;
+        self.cam_vx = 0.0; self.cam_vy = 0.0;
+        self.cam_samples.0.clear();
+    }
 
-#[wasm_bindgen]
-pub fn init(capacity: u32) {
-    ENGINE.with(|e| {
-        *e.borrow_mut() = Some(Engine::new(capacity as usize));
-    });
-}
+    fn clamp_zoom(&self, s: f32) -> f32 {
+        if s.is_finite() && s > 0.0 { s.clamp(self.min_zoom, self.max_zoom) } else { 1.0 }
+    }
 
-#[wasm_bindgen]
-pub fn reset() {
-    ENGINE.with(|e| {
-        if let Some(ref mut eng) = *e.borrow_mut() {
-            eng.reset();
-        }
-    });
-}
+    // Keep the viewport inside the content bounds; content smaller than the viewport is centered.
+    fn clamp_camera(&mut self) {
+        let Some((l, t, r, b)) = self.content else { return; };
+        if self.view_w <= 0.0 || self.view_h <= 0.0 { return; }
+        let s = self.scale;
+        let fit = |pan: f32, lo: f32, hi: f32, len: f32| {
+            let (min, max) = (len - hi * s, -lo * s);
+            if min > max { (min + max) * 0.5 } else { pan.clamp(min, max) }
+        };
+        self.pan_x = fit(self.pan_x, l, r, self.view_w);
+        self.pan_y = fit(self.pan_y, t, b, self.view_h);
+    }
 
-#[wasm_bindgen]
-pub fn set_view(scale: f32) {
-    ENGINE.with(|e| {
-        if let Some(ref mut eng) = *e.borrow_mut() {
-            eng.scale = if scale > 0.0 { scale } else { 1.0 };
-        }
-    });
-}
+    // World point at the middle of the viewport.
+    fn camera_center(&self) -> (f32, f32) {
+        ((self.view_w * 0.5 - self.pan_x) / self.scale, (self.view_h * 0.5 - self.pan_y) / self.scale)
+    }
 
-#[wasm_bindgen]
-pub fn set_view_params(scale: f32, pan_x: f32, pan_y: f32, pixel_ratio: f32) {
-    ENGINE.with(|e| {
+    fn center_camera(&mut self, cx: f32, cy: f32, s: f32) {
+        self.scale = s;
+        self.pan_x = self.view_w * 0.5 - cx * s;
+        self.pan_y = self.view_h * 0.5 - cy * s;
+    }
+
+    // Multiply the zoom, keeping the world point under screen (sx, sy) (pointer coordinates) fixed.
+    fn zoom_at(&mut self, factor: f32, sx: f32, sy: f32) {
+        if !factor.is_finite() || factor <= 0.0 { return; }
+        let pr = if self.pixel_ratio > 0.0 { self.pixel_ratio } else { 1.0 };
+        let (wx, wy) = self.screen_to_world(sx, sy);
+        self.stop_camera();
+        self.scale = self.clamp_zoom(self.scale * factor);
+        self.pan_x = sx / pr - wx * self.scale;
+        self.pan_y = sy / pr - wy * self.scale;
+        self.clamp_camera();
+        self.follow_pointers();
+    }
+
+    // Wheel/trackpad/two-finger pan: move the content by (dx, dy) CSS px.
+    fn pan_by(&mut self, dx: f32, dy: f32) {
+        self.cam_anim = None;
+        self.cam_vx = 0.0; self.cam_vy = 0.0;
+        self.pan_x += dx;
+        self.pan_y += dy;
+        self.clamp_camera();
+        self.cam_samples.record(self.time, self.pan_x, self.pan_y, self.throw_window_s);
+        self.follow_pointers();
+    }
+
+    // End of a pan gesture: keep panning with the recent pan velocity.
+    fn release_pan(&mut self) {
+        (self.cam_vx, self.cam_vy) = self.cam_samples.velocity(self.time, self.throw_window_s);
+        self.cam_samples.0.clear();
+    }
+
+    // Animate the view so the world rect fits inside the viewport minus `padding` CSS px.
+    fn zoom_to_rect(&mut self, l: f32, t: f32, r: f32, b: f32, padding: f32, duration: f32) -> bool {
+        if self.view_w <= 0.0 || self.view_h <= 0.0 || r < l || b < t { return false; }
+        let avail_w = (self.view_w - 2.0 * padding).max(1.0);
+        let avail_h = (self.view_h - 2.0 * padding).max(1.0);
+        let s1 = self.clamp_zoom((avail_w / (r - l).max(1e-3)).min(avail_h / (b - t).max(1e-3)));
+        let (cx1, cy1) = ((l + r) * 0.5, (t + b) * 0.5);
+        self.stop_camera();
+        if duration > 0.0 {
+            let (cx0, cy0) = self.camera_center();
+            self.cam_anim = Some(CameraAnim { cx0, cy0, s0: self.scale, cx1, cy1, s1, t: 0.0, duration });
+        } else {
+            self.center_camera(cx1, cy1, s1);
+            self.clamp_camera();
+        }
+        true
+    }
+
+    // Zoom to the union of the nodes' boxes (every node when `ids` is empty).
+    fn fit_to_nodes(&mut self, ids: &[i32], padding: f32, duration: f32) -> bool {
+        let mut rect: Option<(f32, f32, f32, f32)> = None;
+        for n in &self.nodes {
+            if !ids.is_empty() && !ids.contains(&n.id) { continue; }
+            let (l, t, r, b) = n.aabb();
+            rect = Some(match rect {
+                Some((l0, t0, r0, b0)) => (l0.min(l), t0.min(t), r0.max(r), b0.max(b)),
+                None => (l, t, r, b),
+            });
+        }
+        let Some((l, t, r, b)) = rect else { return false; };
+        self.zoom_to_rect(l, t, r, b, padding, duration)
+    }
+
+    // Advance a camera animation, or coast with pan inertia.
+    fn step_camera(&mut self, dt: f32) {
+        let dt = dt.max(0.0);
+        if let Some(mut a) = self.cam_anim {
+            a.t += dt;
+            let k = ease_in_out((a.t / a.duration).min(1.0));
+            // Zoom geometrically so the rate of scale change looks even
+            let s = a.s0 * (a.s1 / a.s0).powf(k);
+            self.center_camera(a.cx0 + (a.cx1 - a.cx0) * k, a.cy0 + (a.cy1 - a.cy0) * k, s);
+            self.clamp_camera();
+            self.cam_anim = if a.t < a.duration { Some(a) } else { None };
+            self.follow_pointers();
+            return;
+        }
+        if self.cam_vx == 0.0 && self.cam_vy == 0.0 { return; }
+        self.pan_x += self.cam_vx * dt;
+        self.pan_y += self.cam_vy * dt;
+        let (px, py) = (self.pan_x, self.pan_y);
+        self.clamp_camera();
+        // Stop at the content edge instead of pushing against it
+        if self.pan_x != px { self.cam_vx = 0.0; }
+        if self.pan_y != py { self.cam_vy = 0.0; }
+        let damp = if self.cam_damping < 1.0 { self.cam_damping.powf(dt) } else { 1.0 };
+        self.cam_vx *= damp; self.cam_vy *= damp;
+        self.follow_pointers();
+        // Below a pixel per second the camera is at rest
+        if self.cam_vx.abs() < 1.0 { self.cam_vx = 0.0; }
+        if self.cam_vy.abs() < 1.0 { self.cam_vy = 0.0; }
+    }
+
+    // Animate a node property toward `to`: eased over `duration` seconds, or as a spring
+    // (stiffness, damping) when `spring` is set. Replaces a running animation of the same
+    // property; refused while the node is held or for a spring without positive stiffness.
+    fn animate(&mut self, id: i32, prop: AnimProp, to: f32, duration: f32, easing: u32, spring: Option<(f32, f32)>) -> bool {
+        let Some(&idx) = self.index.get(&id) else { return false; };
+        let n = &self.nodes[idx];
+        if n.grabbing || n.resize.is_some() || !to.is_finite() { return false; }
+        if spring.is_some_and(|(k, c)| !(k > 0.0 && k.is_finite() && c.is_finite())) { return false; }
+        if let Some(i) = self.tweens.iter().position(|t| t.node == id && t.prop == prop) {
+            let old = self.tweens.remove(i);
+            // Event: anim_end(nodeId, property, cancelled)
+            self.events.push_with(EventKind::AnimEnd, [id, prop as i32, 1], [old.value, 0.0, 0.0, 0.0]);
+        }
+        let n = &mut self.nodes[idx];
+        if matches!(prop, AnimProp::X | AnimProp::Y) { n.vx = 0.0; n.vy = 0.0; }
+        let from = prop.get(n);
+        let spring = spring.map(|(k, c)| (k, c.max(0.0)));
+        if spring.is_none() && duration <= 0.0 {
+            self.history.touch_node(id, Some(n));
+            prop.set(n, to);
+            self.events.push_with(EventKind::AnimEnd, [id, prop as i32, 0], [to, 0.0, 0.0, 0.0]);
+            return true;
+        }
+        self.tweens.push(Tween { node: id, prop, from, to, value: from, t: 0.0, duration, easing, spring, vel: 0.0 });
+        true
+    }
+
+    // Stop the node's animations where they are; returns how many were running.
+    fn cancel_tweens(&mut self, id: i32) -> u32 {
+        let mut count = 0;
+        for t in self.tweens.iter().filter(|t| t.node == id) {
+            self.events.push_with(EventKind::AnimEnd, [id, t.prop as i32, 1], [t.value, 0.0, 0.0, 0.0]);
+            count += 1;
+        }
+        self.tweens.retain(|t| t.node != id);
+        count
+    }
+
+    fn advance_tweens(&mut self, dt: f32) {
+        if self.tweens.is_empty() { return; }
+        let dt = dt.max(0.0);
+        let mut tweens = std::mem::take(&mut self.tweens);
+        tweens.retain_mut(|tw| {
+            let Some(&idx) = self.index.get(&tw.node) else { return false; };
+            let done = match tw.spring {
+                Some((k, c)) => {
+                    // Semi-implicit Euler in <= 1/120 s substeps keeps stiff springs stable;
+                    // a long frame simulates at most a second
+                    let sim = dt.min(1.0);
+                    let steps = (sim * 120.0).ceil().max(1.0);
+                    let h = sim / steps;
+                    for _ in 0..steps as u32 {
+                        tw.vel += (-k * (tw.value - tw.to) - c * tw.vel) * h;
+                        tw.value += tw.vel * h;
+                    }
+                    tw.t += dt;
+                    let settled = (tw.value - tw.to).abs() < 1e-3 && tw.vel.abs() < 1e-3;
+                    // Undamped (or barely damped) springs would ring forever
+                    settled || tw.t >= SPRING_MAX_S || !tw.value.is_finite()
+                }
+                None => {
+                    tw.t += dt;
+                    let k = (tw.t / tw.duration).min(1.0);
+                    tw.value = tw.from + (tw.to - tw.from) * ease(tw.easing, k);
+                    k >= 1.0
+                }
+            };
+            if done { tw.value = tw.to; }
+            let n = &mut self.nodes[idx];
+            self.history.touch_node(n.id, Some(n));
+            tw.prop.set(n, tw.value);
+            if done {
+                self.events.push_with(EventKind::AnimEnd, [tw.node, tw.prop as i32, 0], [tw.to, 0.0, 0.0, 0.0]);
+            }
+            !done
+        });
+        self.tweens = tweens;
+    }
+
+    // Note a node's state before it is edited inside the open change.
+    fn touch(&mut self, id: i32) {
+        let n = self.index.get(&id).map(|&i| &self.nodes[i]);
+        self.history.touch_node(id, n);
+    }
+
+    fn touch_path(&mut self, id: i32) {
+        self.history.touch_path(id, self.draw_paths.get(&id));
+    }
+
+    // Open a change; nested opens (gestures, groups, strokes) merge into the outermost one.
+    fn begin_change(&mut self) {
+        self.end_gesture(true);
+        if self.history.open == 0 && self.history.depth > 0 { self.history.base = Some(Default::default()); }
+        self.history.open += 1;
+    }
+
+    fn end_change(&mut self) {
+        if self.history.open == 0 { return; }
+        self.history.open -= 1;
+        if self.history.open > 0 { return; }
+        let Some((nodes0, paths0)) = self.history.base.take() else { return; };
+        let mut changes = Vec::new();
+        for (id, a) in nodes0 {
+            let b = self.index.get(&id).map(|&i| self.nodes[i].state());
+            if a != b { changes.push(Change::Node(id, a, b)); }
+        }
+        for (id, a) in paths0 {
+            let b = self.draw_paths.get(&id);
+            if a.as_ref() != b { changes.push(Change::Path(id, a, b.cloned())); }
+        }
+        if changes.is_empty() { return; }
+        self.history.redo.clear();
+        self.history.undo.push(changes);
+        let over = self.history.undo.len().saturating_sub(self.history.depth);
+        self.history.undo.drain(..over);
+    }
+
+    // Close a released gesture once the nodes it moved are at rest; `force` closes it regardless
+    // (something else is about to be recorded). No-op while a pointer still holds a node.
+    fn end_gesture(&mut self, force: bool) {
+        if !self.history.gesture || self.pointers.values().any(|p| p.node.is_some()) { return; }
+        if !force {
+            let moving = |id: &i32| self.tweens.iter().any(|t| t.node == *id)
+                || self.index.get(id).is_some_and(|&i| {
+                    let n = &self.nodes[i];
+                    n.grabbing || n.resize.is_some() || n.vx != 0.0 || n.vy != 0.0
+                });
+            if self.history.base.as_ref().is_some_and(|(nodes, _)| nodes.keys().any(moving)) { return; }
+        }
+        self.history.gesture = false;
+        self.end_change();
+    }
+
+    // Run `f` as one undo step (or as part of the open one).
+    fn record<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        if self.history.paused { return f(self); }
+        self.begin_change();
+        let out = f(self);
+        self.end_change();
+        out
+    }
+
+    fn set_history_depth(&mut self, depth: usize) {
+        self.history.depth = depth;
+        let over = self.history.undo.len().saturating_sub(depth);
+        self.history.undo.drain(..over);
+        if depth == 0 { self.history.redo.clear(); }
+    }
+
+    // A released gesture still settling counts as the newest step: undo closes it first.
+    fn can_undo(&self) -> bool { !self.history.undo.is_empty() || self.settling_change() }
+    fn can_redo(&self) -> bool { !self.history.redo.is_empty() && !self.settling_change() }
+
+    fn settling_change(&self) -> bool {
+        if !self.history.gesture || self.history.open != 1 || self.pointers.values().any(|p| p.node.is_some()) { return false; }
+        let Some((nodes, _)) = &self.history.base else { return false; };
+        nodes.iter().any(|(id, s)| self.index.get(id).map(|&i| self.nodes[i].state()) != *s)
+    }
+
+    // Step back (or forward with `redo`); refused while a change is open.
+    fn undo(&mut self) -> bool { self.travel(false) }
+    fn redo(&mut self) -> bool { self.travel(true) }
+
+    fn travel(&mut self, forward: bool) -> bool {
+        self.end_gesture(true);
+        if self.history.open > 0 { return false; }
+        let entry = if forward { self.history.redo.pop() } else { self.history.undo.pop() };
+        let Some(entry) = entry else { return false; };
+        let mut apply = |c: &Change| match c {
+            Change::Node(id, before, after) => self.restore_node(*id, if forward { after } else { before }.as_ref()),
+            Change::Path(id, before, after) => match if forward { after } else { before } {
+                Some(p) => { self.draw_paths.insert(*id, p.clone()); }
+                None => { self.draw_paths.remove(id); }
+            },
+        };
+        if forward { entry.iter().for_each(&mut apply); } else { entry.iter().rev().for_each(&mut apply); }
+        if forward { self.history.undo.push(entry); } else { self.history.redo.push(entry); }
+        self.sync_hierarchy();
+        true
+    }
+
+    fn restore_node(&mut self, id: i32, state: Option<&NodeState>) {
+        let Some(s) = state else { self.remove_node(id); return; };
+        self.cancel_tweens(id);
+        let idx = match self.index.get(&id) {
+            Some(&idx) => idx,
+            None => {
+                self.nodes.push(Node::new(id));
+                self.index.insert(id, self.nodes.len() - 1);
+                self.nodes.len() - 1
+            }
+        };
+        let n = &mut self.nodes[idx];
+        n.restore(s);
+        self.next_z = self.next_z.max(s.z.saturating_add(1));
+        self.grid.insert(id, n.aabb());
+    }
+
+    // Drop a node and everything keyed by its id; children stay where they are as roots.
+    fn remove_node(&mut self, id: i32) -> bool {
+        let Some(&idx) = self.index.get(&id) else { return false; };
+        self.touch(id);
+        self.cancel_tweens(id);
+        self.set_drop_target(idx, None);
+        for n in &mut self.nodes {
+            if n.parent == Some(id) {
+                self.history.touch_node(n.id, Some(n));
+                n.parent = None;
+            }
+            if n.group_leader == Some(id) { n.group_leader = None; n.grabbing = false; }
+        }
+        for p in self.pointers.values_mut() {
+            if p.node == Some(id) { p.node = None; }
+        }
+        let touching = self.contacts.iter().filter(|&&(a, b)| a != id && b != id).copied().collect();
+        self.update_contacts(touching);
+        self.selection.remove(&id);
+        self.grid.remove(id);
+        self.index.remove(&id);
+        self.nodes.swap_remove(idx);
+        if let Some(moved) = self.nodes.get(idx) { self.index.insert(moved.id, idx); }
+        true
+    }
+
+    // Emit swipe when a released pointer travelled far enough and was moving fast at release.
+    fn detect_swipe(&mut self, p: &Pointer, id: i32) {
+        if self.swipe_min_speed <= 0.0 { return; }
+        let (dx, dy) = (p.x - p.down_x, p.y - p.down_y);
+        if (dx*dx + dy*dy).sqrt() < self.swipe_min_dist { return; }
+        let (vx, vy) = p.samples.velocity(self.time, self.throw_window_s);
+        let speed = (vx*vx + vy*vy).sqrt();
+        if speed < self.swipe_min_speed { return; }
+        let dir = if vx.abs() >= vy.abs() { if vx >= 0.0 { 0 } else { 2 } } else if vy >= 0.0 { 1 } else { 3 };
+        // Event: swipe(nodeId, direction, speed)
+        self.events.push_with(EventKind::Swipe, [id, dir, speed.round() as i32], [vx, vy, 0.0, 0.0]);
+    }
+
+    fn upsert_drop_zones(&mut self, data: &[f32]) {
+        // [id, x, y, w, h, acceptMask, mode] * Z
+        if !data.len().is_multiple_of(7) { return; }
+        for c in data.chunks(7) {
+            let z = DropZone {
+                id: c[0] as i32,
+                l: c[1], t: c[2], r: c[1] + c[3].max(0.0), b: c[2] + c[4].max(0.0),
+                accept: c[5] as u32,
+                by_pointer: c[6] < 0.5,
+            };
+            match self.drop_zones.iter_mut().find(|o| o.id == z.id) {
+                Some(o) => *o = z,
+                None => self.drop_zones.push(z),
+            }
+        }
+    }
+
+    fn remove_drop_zone(&mut self, id: i32) -> bool {
+        let before = self.drop_zones.len();
+        self.drop_zones.retain(|z| z.id != id);
+        // Nodes hovering the zone leave it
+        for idx in 0..self.nodes.len() {
+            if self.nodes[idx].drop_zone == Some(id) { self.set_drop_target(idx, None); }
+        }
+        self.drop_zones.len() != before
+    }
+
+    // Topmost zone accepting the node at the given pointer position.
+    fn zone_under(&self, idx: usize, px: f32, py: f32) -> Option<i32> {
+        let n = &self.nodes[idx];
+        let (l, t, r, b) = n.aabb();
+        self.drop_zones.iter().rev()
+            .filter(|z| z.accept == 0 || n.flags & z.accept != 0)
+            .find(|z| if z.by_pointer {
+                px >= z.l && px <= z.r && py >= z.t && py <= z.b
+            } else {
+                l < z.r && r > z.l && t < z.b && b > z.t
+            })
+            .map(|z| z.id)
+    }
+
+    fn set_drop_target(&mut self, idx: usize, zone: Option<i32>) {
+        let n = &mut self.nodes[idx];
+        if n.drop_zone == zone { return; }
+        // Event: drop_leave(nodeId, zoneId) / drop_enter(nodeId, zoneId)
+        if let Some(old) = n.drop_zone { self.events.push(EventKind::DropLeave, [n.id, old, 0]); }
+        if let Some(new) = zone { self.events.push(EventKind::DropEnter, [n.id, new, 0]); }
+        n.drop_zone = zone;
+    }
+
+    // Track which zone each dragged node (not carried ones) is over.
+    fn update_drop_targets(&mut self) {
+        if self.drop_zones.is_empty() { return; }
+        for idx in 0..self.nodes.len() {
+            let n = &self.nodes[idx];
+            if !n.grabbing || n.group_leader.is_some() { continue; }
+            let Some(p) = self.pointers.get(&n.grab_pointer) else { continue; };
+            let zone = self.zone_under(idx, p.x, p.y);
+            self.set_drop_target(idx, zone);
+        }
+    }
+
+    // On release: settle the hover at the release point, then drop into that zone.
+    fn drop_at(&mut self, idx: usize, px: f32, py: f32) {
+        if self.drop_zones.is_empty() { return; }
+        let zone = self.zone_under(idx, px, py);
+        self.set_drop_target(idx, zone);
+        let Some(zone) = zone else { return; };
+        let n = &mut self.nodes[idx];
+        n.drop_zone = None;
+        // Event: drop(nodeId, zoneId)
+        self.events.push_with(EventKind::Drop, [n.id, zone, 0], [n.x, n.y, px, py]);
+    }
+
+    // Fire long_press once for each pointer held still on a node past the threshold.
+    fn detect_long_presses(&mut self) {
+        if self.long_press_s <= 0.0 { return; }
+        let mut held: Vec<(i32, i32, f32, f32)> = self.pointers.iter()
+            .filter(|(_, p)| !p.long_pressed && p.max_move <= self.move_thresh_px && self.time - p.down_time >= self.long_press_s)
+            .filter_map(|(&pid, p)| {
+                let id = p.node?;
+                let n = &self.nodes[*self.index.get(&id)?];
+                // Not while the pointer is part of a pinch or resize
+                (n.pinch.is_none() && n.resize.is_none()).then_some((pid, id, p.x, p.y))
+            })
+            .collect();
+        held.sort_unstable_by_key(|h| h.0);
+        for (pid, id, x, y) in held {
+            if let Some(p) = self.pointers.get_mut(&pid) { p.long_pressed = true; }
+            // Event: long_press(nodeId, pointerId)
+            self.events.push_with(EventKind::LongPress, [id, pid, 0], [x, y, 0.0, 0.0]);
+        }
+    }
+
+    // Snap dragged nodes' edges (and centers) to those of resting nodes within the
+    // threshold, then record every line the dragged node ends up aligned on. A
+    // group snaps as one box (its leader's union with the carried nodes).
+    fn apply_guides(&mut self) {
+        self.guides.clear();
+        if self.guide_threshold <= 0.0 { return; }
+        if !self.nodes.iter().any(|n| n.grabbing && n.pinch.is_none()) { return; }
+        let centers = self.guide_centers;
+        // Edge (and center) lines of a span, with how many are in use
+        let lines = |lo: f32, hi: f32| if centers { ([lo, (lo + hi) * 0.5, hi], 3) } else { ([lo, hi, hi], 2) };
+        // Smallest offset moving any of `from` onto any of `to`
+        let nearest = |from: &[f32], to: &[f32], best: &mut f32| {
+            for &a in from {
+                for &c in to {
+                    if (c - a).abs() < best.abs() { *best = c - a; }
+                }
+            }
+        };
+        // Guide sources: idle nodes in view (everywhere while the viewport size is unknown)
+        let (vl, vt, vr, vb) = self.visible_world_rect()
+            .unwrap_or((f32::NEG_INFINITY, f32::NEG_INFINITY, f32::INFINITY, f32::INFINITY));
+        let mut ids = Vec::new();
+        self.grid.query(vl, vt, vr, vb, &mut ids);
+        let others: Vec<_> = ids.iter()
+            .filter_map(|id| self.index.get(id).map(|&i| &self.nodes[i]))
+            .filter(|o| !o.grabbing)
+            .filter_map(|o| {
+                let (ol, ot, or, ob) = o.aabb();
+                (ol <= vr && or >= vl && ot <= vb && ob >= vt).then(|| (o.id, lines(ol, or), lines(ot, ob)))
+            })
+            .collect();
+        for i in 0..self.nodes.len() {
+            let n = &self.nodes[i];
+            if !n.grabbing || n.pinch.is_some() || n.group_leader.is_some() { continue; }
+            let (l, t, r, b) = self.group_aabb(i);
+            let ((xs, nx), (ys, ny)) = (lines(l, r), lines(t, b));
+            let (mut dx, mut dy) = (f32::INFINITY, f32::INFINITY);
+            for (_, (oxs, onx), (oys, ony)) in &others {
+                nearest(&xs[..nx], &oxs[..*onx], &mut dx);
+                nearest(&ys[..ny], &oys[..*ony], &mut dy);
+            }
+            let dx = if dx.abs() <= self.guide_threshold { dx } else { 0.0 };
+            let dy = if dy.abs() <= self.guide_threshold { dy } else { 0.0 };
+            if dx != 0.0 || dy != 0.0 { self.shift_group(i, dx, dy); }
+            let id = self.nodes[i].id;
+            let (l, t, r, b) = self.group_aabb(i);
+            let (xs, ys) = (lines(l, r), lines(t, b));
+            for (oid, ox, oy) in &others {
+                for (axis, (mine, n), (theirs, m)) in [(0.0, xs, *ox), (1.0, ys, *oy)] {
+                    for &p in &mine[..n] {
+                        if theirs[..m].iter().any(|&c| (c - p).abs() <= 1e-3) {
+                            self.guides.extend_from_slice(&[axis, p, id as f32, *oid as f32]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // World rect under the viewport, once its size is known.
+    fn visible_world_rect(&self) -> Option<(f32, f32, f32, f32)> {
+        if self.view_w <= 0.0 || self.view_h <= 0.0 { return None; }
+        let s = self.scale.max(1e-4);
+        Some((-self.pan_x / s, -self.pan_y / s, (self.view_w - self.pan_x) / s, (self.view_h - self.pan_y) / s))
+    }
+
+    // Node indices with every parent ahead of its children.
+    fn hierarchy_order(&self) -> Vec<usize> {
+        let mut depth = vec![0usize; self.nodes.len()];
+        for (i, d) in depth.iter_mut().enumerate() {
+            let mut cur = self.nodes[i].parent;
+            while let Some(pid) = cur {
+                let Some(&pi) = self.index.get(&pid) else { break; };
+                *d += 1;
+                if *d > self.nodes.len() { break; }
+                cur = self.nodes[pi].parent;
+            }
+        }
+        let mut order: Vec<usize> = (0..self.nodes.len()).collect();
+        order.sort_by_key(|&i| depth[i]);
+        order
+    }
+
+    // Re-place children from their parents; nodes moved directly instead
+    // (dragged, upserted) get a fresh local transform.
+    fn sync_hierarchy(&mut self) {
+        if self.nodes.iter().all(|n| n.parent.is_none()) { return; }
+        for i in self.hierarchy_order() {
+            let Some(pid) = self.nodes[i].parent else { continue; };
+            let Some(&pi) = self.index.get(&pid) else { continue; };
+            let p = self.nodes[pi].clone();
+            let n = &mut self.nodes[i];
+            if n.grabbing || n.local_dirty {
+                n.local = p.local_of(n);
+            } else {
+                let before = self.history.before(n);
+                p.place_child(n);
+                self.history.touch_changed(n, before);
+            }
+            n.local_dirty = false;
+            self.grid.insert(n.id, n.aabb());
+        }
+    }
+
+    // Attach `child` under `parent` (or detach with None) without moving it in world space.
+    fn set_parent(&mut self, child: i32, parent: Option<i32>) -> bool {
+        let Some(&ci) = self.index.get(&child) else { return false; };
+        if let Some(pid) = parent {
+            // Reject unknown parents and cycles
+            let mut cur = Some(pid);
+            while let Some(id) = cur {
+                if id == child { return false; }
+                let Some(&idx) = self.index.get(&id) else { return false; };
+                cur = self.nodes[idx].parent;
+            }
+        }
+        let n = &mut self.nodes[ci];
+        self.history.touch_node(child, Some(n));
+        n.parent = parent;
+        n.local = Local { x: n.x, y: n.y, angle: n.angle, scale_x: n.scale_x, scale_y: n.scale_y };
+        n.local_dirty = true;
+        self.sync_hierarchy();
+        true
+    }
+
+    fn group(&mut self, parent: i32, ids: &[i32]) -> u32 {
+        ids.iter().filter(|&&id| id != parent && self.set_parent(id, Some(parent))).count() as u32
+    }
+
+    fn ungroup(&mut self, parent: i32) -> u32 {
+        let children: Vec<i32> = self.nodes.iter().filter(|n| n.parent == Some(parent)).map(|n| n.id).collect();
+        children.iter().filter(|&&id| self.set_parent(id, None)).count() as u32
+    }
+
+    // Push overlapping collide nodes apart along the axis of least penetration.
+    // Lower priority moves: free < grabbed < pinned; equal priority splits the push.
+    fn resolve_collisions(&mut self) {
+        const PASSES: usize = 4;
+        const CONTACT_SLOP: f32 = 0.5;
+        let priority = |n: &Node| if n.flags & FLAG_PINNED != 0 || (n.parent.is_some() && !n.grabbing) { 2 } else if n.grabbing || n.resize.is_some() { 1 } else { 0 };
+        let mut touching = HashSet::new();
+        let mut ids = Vec::new();
+        for _ in 0..PASSES {
+            let mut moved = false;
+            for i in 0..self.nodes.len() {
+                if self.nodes[i].flags & FLAG_COLLIDE == 0 { continue; }
+                let (l, t, r, b) = self.nodes[i].aabb();
+                ids.clear();
+                self.grid.query(l - CONTACT_SLOP, t - CONTACT_SLOP, r + CONTACT_SLOP, b + CONTACT_SLOP, &mut ids);
+                for &other in &ids {
+                    let Some(&j) = self.index.get(&other) else { continue; };
+                    if j <= i || self.nodes[j].flags & FLAG_COLLIDE == 0 { continue; }
+                    let (l, t, r, b) = self.nodes[i].aabb();
+                    let (ol, ot, or, ob) = self.nodes[j].aabb();
+                    let (ox, oy) = (r.min(or) - l.max(ol), b.min(ob) - t.max(ot));
+                    if ox < -CONTACT_SLOP || oy < -CONTACT_SLOP { continue; }
+                    // Separated pairs end up edge to edge, so they stay in contact
+                    let (ia, ib) = (self.nodes[i].id, self.nodes[j].id);
+                    touching.insert((ia.min(ib), ia.max(ib)));
+                    if ox <= 0.0 || oy <= 0.0 { continue; }
+                    let (pi, pj) = (priority(&self.nodes[i]), priority(&self.nodes[j]));
+                    if pi == 2 && pj == 2 { continue; }
+                    let (wi, wj) = if pi < pj { (1.0, 0.0) } else if pi > pj { (0.0, 1.0) } else { (0.5, 0.5) };
+                    // Separation direction for node i
+                    let (nx, ny, depth) = if ox < oy {
+                        (if l + r < ol + or { -1.0 } else { 1.0 }, 0.0, ox)
+                    } else {
+                        (0.0, if t + b < ot + ob { -1.0 } else { 1.0 }, oy)
+                    };
+                    for (k, w, sign) in [(i, wi, 1.0f32), (j, wj, -1.0f32)] {
+                        if w == 0.0 { continue; }
+                        let n = &mut self.nodes[k];
+                        self.history.touch_node(n.id, Some(n));
+                        n.x += nx * sign * depth * w;
+                        n.y += ny * sign * depth * w;
+                        // Cancel velocity heading into the other node
+                        if n.vx * nx * sign < 0.0 { n.vx = 0.0; }
+                        if n.vy * ny * sign < 0.0 { n.vy = 0.0; }
+                        n.clamp_to(self.left, self.top, self.right, self.bottom);
+                        self.grid.insert(n.id, n.aabb());
+                    }
+                    moved = true;
+                }
+            }
+            if !moved { break; }
+        }
+        self.update_contacts(touching);
+    }
+
+    fn update_contacts(&mut self, touching: HashSet<(i32, i32)>) {
+        let mut started: Vec<_> = touching.difference(&self.contacts).copied().collect();
+        let mut ended: Vec<_> = self.contacts.difference(&touching).copied().collect();
+        started.sort_unstable();
+        ended.sort_unstable();
+        // Event: collision_start(a, b) / collision_end(a, b)
+        for (a, b) in started { self.events.push(EventKind::CollisionStart, [a, b, 0]); }
+        for (a, b) in ended { self.events.push(EventKind::CollisionEnd, [a, b, 0]); }
+        self.contacts = touching;
+    }
+
+    fn write_transforms(&self) -> Float32Array {
+        let out = self.transforms();
+        let arr = Float32Array::new_with_length(out.len() as u32);
+        arr.copy_from(&out[..]);
+        arr
+    }
+
+    fn transforms(&self) -> Vec<f32> {
+        // [id, x, y, angle, scaleX, scaleY, reserved]
+        let stride = 7usize;
+        let mut out: Vec<f32> = Vec::with_capacity(self.nodes.len() * stride);
+        for i in self.stacking() {
+            let [x, y, angle, scale_x, scale_y, ..] = self.output_pose(i);
+            out.push(self.nodes[i].id as f32);
+            out.push(x);
+            out.push(y);
+            out.push(angle);
+            out.push(self.scale.max(0.0001) * scale_x); // scaleX
+            out.push(self.scale.max(0.0001) * scale_y); // scaleY
+            out.push(0.0); // reserved
+        }
+        out
+    }
+
+    fn write_sizes(&self) -> Float32Array {
+        let out = self.sizes();
+        let arr = Float32Array::new_with_length(out.len() as u32);
+        arr.copy_from(&out[..]);
+        arr
+    }
+
+    fn sizes(&self) -> Vec<f32> {
+        // [id, w, h]
+        let mut out: Vec<f32> = Vec::with_capacity(self.nodes.len() * 3);
+        for i in self.stacking() {
+            let pose = self.output_pose(i);
+            out.extend_from_slice(&[self.nodes[i].id as f32, pose[5], pose[6]]);
+        }
+        out
+    }
+
+    fn write_view(&self) -> Float32Array {
+        // [scale, panX, panY, pixelRatio]
+        let out = [self.scale, self.pan_x, self.pan_y, self.pixel_ratio];
+        let arr = Float32Array::new_with_length(out.len() as u32);
+        arr.copy_from(&out[..]);
+        arr
+    }
+
+    fn write_locals(&self) -> Float32Array {
+        let out = self.locals();
+        let arr = Float32Array::new_with_length(out.len() as u32);
+        arr.copy_from(&out[..]);
+        arr
+    }
+
+    fn locals(&self) -> Vec<f32> {
+        // [id, parentId, x, y, angle, scaleX, scaleY]
+        let mut out: Vec<f32> = Vec::with_capacity(self.nodes.len() * 7);
+        for i in self.stacking() {
+            let n = &self.nodes[i];
+            let pose = self.output_pose(i);
+            // Roots are relative to the world
+            let l = if n.parent.is_some() { &pose[7..12] } else { &pose[..5] };
+            out.extend_from_slice(&[n.id as f32, n.parent.unwrap_or(-1) as f32]);
+            out.extend_from_slice(l);
+        }
+        out
+    }
+
+    fn write_guides(&self) -> Float32Array {
+        // [axis, position, nodeId, otherNodeId] * G
+        let arr = Float32Array::new_with_length(self.guides.len() as u32);
+        arr.copy_from(&self.guides[..]);
+        arr
+    }
+
+    fn write_particles(&self) -> Float32Array {
+        // [x, y, vx, vy, r, life] * N
+        let stride = 6usize;
+        let mut out: Vec<f32> = Vec::with_capacity(self.particles.len() * stride);
+        for p in &self.particles {
+            out.push(p.x);
+            out.push(p.y);
+            out.push(p.vx);
+            out.push(p.vy);
+            out.push(p.r);
+            out.push(p.life);
+        }
+        let arr = Float32Array::new_with_length(out.len() as u32);
+        arr.copy_from(&out[..]);
+        arr
+    }
+
+    fn write_draw_paths(&self) -> Float32Array {
+        // Format: [path_id, color, width, closed, point_count, x1, y1, pressure1, timestamp1, x2, y2, ...] per path
+        let mut out: Vec<f32> = Vec::new();
+        for path in self.draw_paths.values() {
+            out.push(path.id as f32);
+            out.push(path.color as f32);
+            out.push(path.width);
+            out.push(if path.closed { 1.0 } else { 0.0 });
+            out.push((path.points.len() / 4) as f32); // point count
+            out.extend_from_slice(&path.points);
+        }
+        let arr = Float32Array::new_with_length(out.len() as u32);
+        arr.copy_from(&out[..]);
+        arr
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::default();
+        w.0.extend_from_slice(STATE_MAGIC);
+        w.u32(STATE_VERSION);
+        for v in [self.scale, self.pan_x, self.pan_y, self.pixel_ratio] { w.f32(v); }
+        for v in [self.left, self.top, self.right, self.bottom, self.grid_x, self.grid_y, self.inertia, self.damping] { w.f32(v); }
+        for v in [self.g_x, self.g_y, self.p_damping, self.restitution] { w.f32(v); }
+        w.u32(self.nodes.len() as u32);
+        for n in &self.nodes {
+            w.i32(n.id);
+            for v in [n.x, n.y, n.w, n.h, n.vx, n.vy] { w.f32(v); }
+            w.u32(n.flags);
+            for v in [n.angle, n.scale_x, n.scale_y, n.pivot_x, n.pivot_y] { w.f32(v); }
+            w.i32(n.z);
+            w.i32(n.parent.unwrap_or(-1));
+        }
+        let mut paths: Vec<&DrawPath> = self.draw_paths.values().collect();
+        paths.sort_unstable_by_key(|p| p.id);
+        w.u32(paths.len() as u32);
+        for p in paths {
+            w.i32(p.id);
+            w.u32(p.color);
+            w.f32(p.width);
+            w.0.push(p.closed as u8);
+            w.u32((p.points.len() / 4) as u32);
+            for &v in &p.points[..p.points.len() / 4 * 4] { w.f32(v); }
+        }
+        w.u32(self.particles.len() as u32);
+        for p in &self.particles {
+            for v in [p.x, p.y, p.vx, p.vy, p.r, p.life] { w.f32(v); }
+        }
+        let mut images: Vec<(&i32, &Image)> = self.images.iter().collect();
+        images.sort_unstable_by_key(|(&id, _)| id);
+        w.u32(images.len() as u32);
+        for (&id, img) in images {
+            w.i32(id);
+            w.u32(img.w);
+            w.u32(img.h);
+            w.0.extend_from_slice(&img.data);
+        }
+        w.0
+    }
+
+    // Replace the scene with a save_state buffer; false (and nothing changed) when it doesn't parse.
+    // Gestures, animations, selection and history start fresh; drop zones and config are kept.
+    fn load_state(&mut self, bytes: &[u8]) -> bool {
+        let Some(saved) = SavedState::read(bytes) else { return false; };
+        let [scale, pan_x, pan_y, pixel_ratio] = saved.view;
+        self.scale = self.clamp_zoom(scale);
+        self.pan_x = pan_x; self.pan_y = pan_y;
+        self.pixel_ratio = if pixel_ratio > 0.0 { pixel_ratio } else { 1.0 };
+        self.stop_camera();
+        [self.left, self.top, self.right, self.bottom, self.grid_x, self.grid_y, self.inertia, self.damping] = saved.constraints;
+        [self.g_x, self.g_y, self.p_damping, self.restitution] = saved.particle_params;
+        self.nodes.clear();
+        self.index.clear();
+        self.grid.clear();
+        self.pointers.clear();
+        self.selection.clear();
+        self.contacts.clear();
+        self.tweens.clear();
+        self.guides.clear();
+        self.lerp_poses.clear();
+        self.history = History { depth: self.history.depth, ..History::default() };
+        self.next_z = 0;
+        for (id, state, vx, vy) in saved.nodes {
+            let mut n = Node::new(id);
+            n.restore(&state);
+            n.vx = vx; n.vy = vy;
+            self.next_z = self.next_z.max(state.z.saturating_add(1));
+            self.grid.insert(id, n.aabb());
+            self.index.insert(id, self.nodes.len());
+            self.nodes.push(n);
+        }
+        self.sync_hierarchy();
+        self.draw_paths = saved.paths.into_iter().map(|p| (p.id, p)).collect();
+        self.particles = saved.particles;
+        self.images = saved.images.into_iter().collect();
+        true
+    }
+
+    // JSON in the roadmap's CanvasComposition shape. Engine nodes carry no text meaning, so
+    // they go under `settings.nodes` and `textLayers` is left for the app to fill. Stored
+    // images become image layers placed at the node with the same id (else at the origin,
+    // pixel-sized); `url` is left empty since uploads live in the app's image storage.
+    fn composition_json(&self) -> String {
+        use std::fmt::Write;
+        let num = |v: f32| if v.is_finite() { format!("{}", v) } else { "null".to_string() };
+        let (width, height) = if self.right.is_finite() && self.bottom.is_finite() {
+            (self.right - self.left, self.bottom - self.top)
+        } else { (self.view_w, self.view_h) };
+        let mut out = String::new();
+        let _ = write!(out, "{{\"version\":\"{}\",\"canvas\":{{\"width\":{},\"height\":{},\"background\":\"\"}}", STATE_VERSION, num(width), num(height));
+        let mut paths: Vec<&DrawPath> = self.draw_paths.values().collect();
+        paths.sort_unstable_by_key(|p| p.id);
+        out.push_str(",\"drawPaths\":[");
+        for (i, p) in paths.iter().enumerate() {
+            if i > 0 { out.push(','); }
+            let points: Vec<String> = p.points.chunks_exact(4)
+                .map(|q| format!("{{\"x\":{},\"y\":{},\"pressure\":{}}}", num(q[0]), num(q[1]), num(q[2])))
+                .collect();
+            // Packed RGBA has red in the low byte
+            let [r, g, b, a] = p.color.to_le_bytes();
+            let _ = write!(
+                out,
+                "{{\"id\":{},\"points\":[{}],\"color\":\"rgba({}, {}, {}, {})\",\"width\":{},\"closed\":{}}}",
+                p.id, points.join(","), r, g, b, num(a as f32 / 255.0), num(p.width), p.closed
+            );
+        }
+        out.push_str("],\"textLayers\":[],\"imageLayers\":[");
+        let mut images: Vec<(&i32, &Image)> = self.images.iter().collect();
+        images.sort_unstable_by_key(|(&id, _)| id);
+        for (i, (&id, img)) in images.into_iter().enumerate() {
+            if i > 0 { out.push(','); }
+            let (x, y, w, h) = match self.index.get(&id).map(|&idx| &self.nodes[idx]) {
+                Some(n) => (n.x, n.y, n.w, n.h),
+                None => (0.0, 0.0, img.w as f32, img.h as f32),
+            };
+            let _ = write!(out, "{{\"id\":{},\"url\":\"\",\"x\":{},\"y\":{},\"w\":{},\"h\":{}}}", id, num(x), num(y), num(w), num(h));
+        }
+        let _ = write!(
+            out,
+            "],\"settings\":{{\"particlesEnabled\":{},\"view\":{{\"scale\":{},\"panX\":{},\"panY\":{},\"pixelRatio\":{}}},\"constraints\":{{\"left\":{},\"top\":{},\"right\":{},\"bottom\":{},\"gridX\":{},\"gridY\":{},\"inertia\":{},\"damping\":{}}},\"nodes\":[",
+            !self.particles.is_empty(), num(self.scale), num(self.pan_x), num(self.pan_y), num(self.pixel_ratio),
+            num(self.left), num(self.top), num(self.right), num(self.bottom), num(self.grid_x), num(self.grid_y), num(self.inertia), num(self.damping)
+        );
+        for (i, n) in self.stacking().into_iter().map(|i| &self.nodes[i]).enumerate() {
+            if i > 0 { out.push(','); }
+            let parent = n.parent.map_or("null".to_string(), |p| p.to_string());
+            let _ = write!(
+                out,
+                "{{\"id\":{},\"x\":{},\"y\":{},\"w\":{},\"h\":{},\"flags\":{},\"angle\":{},\"scaleX\":{},\"scaleY\":{},\"pivotX\":{},\"pivotY\":{},\"z\":{},\"parent\":{}}}",
+                n.id, num(n.x), num(n.y), num(n.w), num(n.h), n.flags, num(n.angle), num(n.scale_x), num(n.scale_y), num(n.pivot_x), num(n.pivot_y), n.z, parent
+            );
+        }
+        out.push_str("]}}");
+        out
+    }
+
+    // A stroke is one undo step, from start_path to finish_path.
+    fn start_path(&mut self, id: i32, x: f32, y: f32, pressure: f32, color: u32, width: f32) -> bool {
+        if self.history.strokes.insert(id) { self.begin_change(); }
+        self.touch_path(id);
+        self.draw_paths.insert(id, DrawPath { id, points: vec![x, y, pressure, self.time], color, width, closed: false });
+        true
+    }
+
+    fn add_point(&mut self, id: i32, x: f32, y: f32, pressure: f32) -> bool {
+        if !self.draw_paths.contains_key(&id) { return false; }
+        self.touch_path(id);
+        let time = self.time;
+        let Some(path) = self.draw_paths.get_mut(&id) else { return false; };
+        path.points.extend_from_slice(&[x, y, pressure, time]);
+        true
+    }
+
+    fn finish_path(&mut self, id: i32, closed: bool) -> bool {
+        if !self.draw_paths.contains_key(&id) { return false; }
+        self.touch_path(id);
+        if let Some(path) = self.draw_paths.get_mut(&id) { path.closed = closed; }
+        if self.history.strokes.remove(&id) { self.end_change(); }
+        true
+    }
+
+    fn remove_path(&mut self, id: i32) -> bool {
+        let removed = self.record(|eng| {
+            eng.touch_path(id);
+            eng.draw_paths.remove(&id).is_some()
+        });
+        if self.history.strokes.remove(&id) { self.end_change(); }
+        removed
+    }
+
+    fn clear_paths(&mut self) {
+        self.record(|eng| {
+            let ids: Vec<i32> = eng.draw_paths.keys().copied().collect();
+            for id in ids { eng.touch_path(id); }
+            eng.draw_paths.clear();
+        });
+        for _ in std::mem::take(&mut self.history.strokes) { self.end_change(); }
+    }
+
+    // data stride 6: [x, y, vx, vy, r, life]; returns how many were spawned.
+    fn spawn_particles(&mut self, data: &[f32]) -> u32 {
+        let mut count = 0;
+        for chunk in data.chunks_exact(6) {
+            let p = Particle { x: chunk[0], y: chunk[1], vx: chunk[2], vy: chunk[3], r: chunk[4].max(0.1), life: chunk[5].max(0.0) };
+            if p.life > 0.0 { self.particles.push(p); count += 1; }
+        }
+        count
+    }
+
+    fn seed_particles(&mut self, n: usize) {
+        self.particles.clear();
+        for i in 0..n {
+            let x = (i % 800) as f32;
+            let y = (i / 800) as f32;
+            self.particles.push(Particle { x, y, vx: 10.0, vy: -5.0, r: 1.0, life: 10.0 });
+        }
+    }
+
+    fn store_image(&mut self, id: i32, w: u32, h: u32, mut rgba: Vec<u8>) -> bool {
+        if w == 0 || h == 0 { return false; }
+        let expected = (w as usize).saturating_mul(h as usize).saturating_mul(4);
+        if rgba.len() < expected { return false; }
+        rgba.truncate(expected);
+        self.images.insert(id, Image { w, h, data: rgba });
+        true
+    }
+
+    // Record (while recording) and apply one input given as f32 args. Returns the export's
+    // result (bools as 0/1, counts) or 0.
+    fn input(&mut self, kind: Input, args: &[f32]) -> u32 {
+        self.log(kind, || args.to_vec());
+        self.apply_input(kind, args)
+    }
+
+    // Every export that changes engine state logs here first; exports with integer or byte
+    // arguments then make their typed call directly, so `args` is only encoded while recording.
+    fn log(&mut self, kind: Input, args: impl FnOnce() -> Vec<f32>) {
+        let Some(rec) = self.recorder.as_mut() else { return; };
+        let args = args();
+        rec.out.u32(kind as u32);
+        rec.out.u32(rec.frame);
+        rec.out.u32(args.len() as u32);
+        for v in args { rec.out.f32(v); }
+        if kind == Input::Frame { rec.frame += 1; }
+    }
+
+    fn apply_input(&mut self, kind: Input, args: &[f32]) -> u32 {
+        match kind {
+            Input::Frame => self.advance(args.first().copied().unwrap_or(0.0)),
+            Input::Upsert => self.record(|eng| eng.upsert_nodes(args, 8)),
+            Input::UpsertV2 => self.record(|eng| eng.upsert_nodes(args, 13)),
+            Input::Pointers => self.apply_pointers(args),
+            Input::Patch => {
+                let Some((&fields, data)) = args.split_first() else { return 0; };
+                return self.record(|eng| eng.patch_nodes(arg_int(fields) as u32, data));
+            }
+            Input::Remove => return self.record(|eng| eng.remove_nodes(&arg_ints(args))),
+            Input::ClearNodes => self.record(|eng| eng.clear_nodes()),
+            Input::Reset => self.reset(),
+            Input::View => {
+                let ([scale], _) = params(args);
+                self.stop_camera();
+                self.scale = self.clamp_zoom(scale);
+                self.clamp_camera();
+            }
+            Input::ViewParams => {
+                let ([scale, pan_x, pan_y, pixel_ratio], _) = params(args);
+                self.stop_camera();
+                self.scale = self.clamp_zoom(scale);
+                self.pan_x = pan_x;
+                self.pan_y = pan_y;
+                self.pixel_ratio = if pixel_ratio > 0.0 { pixel_ratio } else { 1.0 };
+                self.clamp_camera();
+            }
+            Input::Viewport => {
+                let ([width, height], _) = params(args);
+                self.view_w = width.max(0.0);
+                self.view_h = height.max(0.0);
+            }
+            Input::AutoscrollParams => {
+                let (buf, copy_len) = params::<2>(args);
+                if copy_len >= 1 { self.autoscroll_margin = buf[0].max(0.0); }
+                if copy_len >= 2 { self.autoscroll_speed = buf[1].max(0.0); }
+            }
+            Input::CameraParams => {
+                let (buf, copy_len) = params::<3>(args);
+                if copy_len >= 2 && buf[0] > 0.0 && buf[1] >= buf[0] {
+                    self.min_zoom = buf[0]; self.max_zoom = buf[1];
+                    self.scale = self.clamp_zoom(self.scale);
+                    self.clamp_camera();
+                }
+                if copy_len >= 3 { self.cam_damping = buf[2].clamp(0.0, 1.0); }
+            }
+            Input::CameraBounds => {
+                let ([left, top, right, bottom], _) = params(args);
+                self.content = if right >= left && bottom >= top { Some((left, top, right, bottom)) } else { None };
+                self.clamp_camera();
+            }
+            Input::Constraints => {
+                let (buf, copy_len) = params::<8>(args);
+                if copy_len >= 4 {
+                    self.left = buf[0]; self.top = buf[1]; self.right = buf[2]; self.bottom = buf[3];
+                }
+                if copy_len >= 6 { self.grid_x = buf[4].max(0.0); self.grid_y = buf[5].max(0.0); }
+                if copy_len >= 8 { self.inertia = buf[6].max(0.0); self.damping = buf[7].clamp(0.0, 1.0); }
+            }
+            Input::TapParams => {
+                let (buf, copy_len) = params::<6>(args);
+                if copy_len >= 1 { self.tap_max_s = buf[0].max(0.0); }
+                if copy_len >= 2 { self.move_thresh_px = buf[1].max(0.0); }
+                if copy_len >= 3 { self.double_s = buf[2].max(0.0); }
+                if copy_len >= 4 { self.single_delay_s = buf[3].max(0.0); }
+                if copy_len >= 5 { self.long_press_s = buf[4].max(0.0); }
+                if copy_len >= 6 { self.max_taps = (buf[5] as u32).max(1); }
+            }
+            Input::ThrowParams => {
+                let (buf, copy_len) = params::<2>(args);
+                if copy_len >= 1 { self.throw_window_s = buf[0].max(0.0); }
+                if copy_len >= 2 { self.max_throw_speed = buf[1].max(0.0); }
+            }
+            Input::SwipeParams => {
+                let (buf, copy_len) = params::<2>(args);
+                if copy_len >= 1 { self.swipe_min_speed = buf[0].max(0.0); }
+                if copy_len >= 2 { self.swipe_min_dist = buf[1].max(0.0); }
+            }
+            Input::GuideParams => {
+                let (buf, copy_len) = params::<2>(args);
+                if copy_len >= 1 { self.guide_threshold = buf[0].max(0.0); }
+                if copy_len >= 2 { self.guide_centers = buf[1] > 0.0; }
+            }
+            Input::ResizeParams => {
+                let (buf, copy_len) = params::<5>(args);
+                if copy_len >= 1 { self.handle_px = buf[0].max(0.0); }
+                if copy_len >= 3 { self.min_w = buf[1].max(0.0); self.min_h = buf[2].max(0.0); }
+                if copy_len >= 5 { self.max_w = buf[3].max(0.0); self.max_h = buf[4].max(0.0); }
+            }
+            Input::ParticleParams => {
+                let (buf, copy_len) = params::<4>(args);
+                if copy_len >= 2 { self.g_x = buf[0]; self.g_y = buf[1]; }
+                if copy_len >= 3 { self.p_damping = buf[2].clamp(0.0, 1.0); }
+                if copy_len >= 4 { self.restitution = buf[3].clamp(0.0, 1.0); }
+            }
+            Input::TimestepParams => {
+                let (buf, copy_len) = params::<2>(args);
+                if copy_len >= 1 {
+                    self.fixed_dt = if buf[0] > 0.0 { 1.0 / buf[0] } else { 0.0 };
+                    self.accumulator = 0.0;
+                    self.lerp_poses.clear();
+                }
+                if copy_len >= 2 { self.max_substeps = (buf[1] as u32).max(1); }
+            }
+            Input::DropZones => self.upsert_drop_zones(args),
+            Input::RemoveDropZone => {
+                let ([id], _) = params(args);
+                return self.remove_drop_zone(arg_int(id)) as u32;
+            }
+            Input::ClearDropZones => {
+                let ids: Vec<i32> = self.drop_zones.iter().map(|z| z.id).collect();
+                for id in ids { self.remove_drop_zone(id); }
+            }
+            Input::Clock => {
+                let ([time, accumulator], _) = params(args);
+                self.time = time;
+                self.accumulator = accumulator;
+            }
+            Input::SetParent => {
+                let ([child, parent], _) = params(args);
+                let (child, parent) = (arg_int(child), arg_int(parent));
+                return self.record(|eng| eng.set_parent(child, (parent >= 0).then_some(parent))) as u32;
+            }
+            Input::Group => {
+                let Some((&parent, ids)) = args.split_first() else { return 0; };
+                return self.record(|eng| eng.group(arg_int(parent), &arg_ints(ids)));
+            }
+            Input::Ungroup | Input::BringToFront | Input::SendToBack | Input::Raise | Input::Lower => {
+                let ([id], _) = params(args);
+                let id = arg_int(id);
+                return self.record(|eng| match kind {
+                    Input::Ungroup => eng.ungroup(id),
+                    Input::BringToFront => eng.bring_to_front(id) as u32,
+                    Input::SendToBack => eng.send_to_back(id) as u32,
+                    Input::Raise => eng.shift_z(id, 1) as u32,
+                    _ => eng.shift_z(id, -1) as u32,
+                });
+            }
+            Input::SetZ => {
+                let ([id, z], _) = params(args);
+                return self.set_z_index(arg_int(id), arg_int(z)) as u32;
+            }
+            Input::Select => self.select(&arg_ints(args)),
+            Input::Deselect => self.deselect(&arg_ints(args)),
+            Input::ToggleSelection => self.toggle_selection(&arg_ints(args)),
+            Input::ClearSelection => self.selection.clear(),
+            Input::ZoomAt => {
+                let ([factor, sx, sy], _) = params(args);
+                self.zoom_at(factor, sx, sy);
+            }
+            Input::Pan => {
+                let ([dx, dy], _) = params(args);
+                self.pan_by(dx, dy);
+            }
+            Input::ReleasePan => self.release_pan(),
+            Input::ZoomToRect => {
+                let ([l, t, r, b, padding, duration], _) = params(args);
+                return self.zoom_to_rect(l, t, r, b, padding, duration) as u32;
+            }
+            Input::FitToNodes => {
+                let ([padding, duration], _) = params(args);
+                return self.fit_to_nodes(&arg_ints(args.get(2..).unwrap_or(&[])), padding, duration) as u32;
+            }
+            Input::Animate | Input::Spring => {
+                let ([id, prop, target, a, b], _) = params(args);
+                let Some(prop) = AnimProp::from_code(arg_int(prop) as u32) else { return 0; };
+                return if kind == Input::Spring {
+                    self.animate(arg_int(id), prop, target, 0.0, 0, Some((a, b)))
+                } else {
+                    self.animate(arg_int(id), prop, target, a, arg_int(b) as u32, None)
+                } as u32;
+            }
+            Input::CancelAnimations => {
+                let ([id], _) = params(args);
+                return self.cancel_tweens(arg_int(id));
+            }
+            Input::StartPath => {
+                let ([id, x, y, pressure, color, width], _) = params(args);
+                return self.start_path(arg_int(id), x, y, pressure, arg_int(color) as u32, width) as u32;
+            }
+            Input::AddPoint => {
+                let ([id, x, y, pressure], _) = params(args);
+                return self.add_point(arg_int(id), x, y, pressure) as u32;
+            }
+            Input::FinishPath => {
+                let ([id, closed], _) = params(args);
+                return self.finish_path(arg_int(id), closed > 0.0) as u32;
+            }
+            Input::RemovePath => {
+                let ([id], _) = params(args);
+                return self.remove_path(arg_int(id)) as u32;
+            }
+            Input::ClearPaths => self.clear_paths(),
+            Input::SpawnParticles => return self.spawn_particles(args),
+            Input::ClearParticles => self.particles.clear(),
+            Input::SeedParticles => {
+                let ([n], _) = params(args);
+                self.seed_particles(arg_int(n) as u32 as usize);
+            }
+            Input::StoreImage => {
+                let ([id, w, h], _) = params(args);
+                let rgba = arg_bytes(args.get(3..).unwrap_or(&[]));
+                return self.store_image(arg_int(id), arg_int(w) as u32, arg_int(h) as u32, rgba) as u32;
+            }
+            Input::Undo => return self.undo() as u32,
+            Input::Redo => return self.redo() as u32,
+            Input::BeginGroup => self.begin_change(),
+            Input::EndGroup => self.end_change(),
+            Input::HistoryDepth => {
+                let ([depth], _) = params(args);
+                self.set_history_depth(arg_int(depth) as u32 as usize);
+            }
+            Input::PauseHistory => self.history.paused = args.first().is_some_and(|&v| v > 0.0),
+            Input::ClearHistory => {
+                self.history.undo.clear();
+                self.history.redo.clear();
+            }
+            Input::LoadState => return self.load_state(&arg_bytes(args)) as u32,
+        }
+        0
+    }
+
+    // Config a fresh engine needs to match this one; everything else comes from the snapshot.
+    fn recording_preamble(&self) -> Vec<(Input, Vec<f32>)> {
+        let (l, t, r, b) = self.content.unwrap_or((0.0, 0.0, -1.0, -1.0));
+        let zones = self.drop_zones.iter()
+            .flat_map(|z| [z.id as f32, z.l, z.t, z.r - z.l, z.b - z.t, z.accept as f32, if z.by_pointer { 0.0 } else { 1.0 }])
+            .collect();
+        vec![
+            (Input::Viewport, vec![self.view_w, self.view_h]),
+            (Input::AutoscrollParams, vec![self.autoscroll_margin, self.autoscroll_speed]),
+            (Input::CameraParams, vec![self.min_zoom, self.max_zoom, self.cam_damping]),
+            (Input::CameraBounds, vec![l, t, r, b]),
+            (Input::TapParams, vec![self.tap_max_s, self.move_thresh_px, self.double_s, self.single_delay_s, self.long_press_s, self.max_taps as f32]),
+            (Input::ThrowParams, vec![self.throw_window_s, self.max_throw_speed]),
+            (Input::SwipeParams, vec![self.swipe_min_speed, self.swipe_min_dist]),
+            (Input::GuideParams, vec![self.guide_threshold, if self.guide_centers { 1.0 } else { 0.0 }]),
+            (Input::ResizeParams, vec![self.handle_px, self.min_w, self.min_h, self.max_w, self.max_h]),
+            (Input::TimestepParams, vec![if self.fixed_dt > 0.0 { 1.0 / self.fixed_dt } else { 0.0 }, self.max_substeps as f32]),
+            (Input::DropZones, zones),
+            (Input::HistoryDepth, vec![int_arg(self.history.depth as i32)]),
+            (Input::Clock, vec![self.time, self.accumulator]),
+            (Input::Select, int_args(&self.selection.iter().copied().collect::<Vec<_>>())),
+        ]
+    }
+
+    // Capture inputs from here on; refused (false) unless the engine is at rest, since held pointers,
+    // animations, camera motion and pending taps are not part of the starting snapshot. Undo history
+    // isn't either: undoing past the start replays as a no-op.
+    fn start_recording(&mut self) -> bool {
+        let busy = !self.pointers.is_empty() || !self.tweens.is_empty()
+            || self.cam_anim.is_some() || self.cam_vx != 0.0 || self.cam_vy != 0.0 || !self.cam_samples.0.is_empty()
+            || self.nodes.iter().any(|n| n.single_pending);
+        if busy { return false; }
+        let mut out = StateWriter::default();
+        out.0.extend_from_slice(RECORDING_MAGIC);
+        out.u32(RECORDING_VERSION);
+        let preamble = self.recording_preamble();
+        out.u32(preamble.len() as u32);
+        for (kind, args) in preamble {
+            out.u32(kind as u32);
+            out.u32(args.len() as u32);
+            for v in args { out.f32(v); }
+        }
+        let snapshot = self.save_state();
+        out.u32(snapshot.len() as u32);
+        out.0.extend_from_slice(&snapshot);
+        self.recorder = Some(Recorder { out, frame: 0 });
+        true
+    }
+
+    fn stop_recording(&mut self) -> Vec<u8> {
+        self.recorder.take().map_or_else(Vec::new, |rec| rec.out.0)
+    }
+
+    // Reset to the recording's starting state and queue its inputs for replay_frame.
+    fn load_recording(&mut self, bytes: &[u8]) -> bool {
+        let Some((preamble, snapshot, inputs)) = read_recording(bytes) else { return false; };
+        *self = Engine::new(self.nodes.capacity());
+        // The selection refers to snapshot nodes, so it goes on last
+        let (after, before): (Inputs, Inputs) = preamble.into_iter().partition(|(kind, _)| *kind == Input::Select);
+        for (kind, args) in &before { self.apply_input(*kind, args); }
+        self.load_state(snapshot);
+        for (kind, args) in &after { self.apply_input(*kind, args); }
+        self.replay = inputs.into();
+        true
+    }
+
+    // Apply queued inputs through the next recorded frame; false once the recording is used up.
+    fn replay_frame(&mut self) -> bool {
+        while let Some((kind, args)) = self.replay.pop_front() {
+            self.input(kind, &args);
+            if kind == Input::Frame { return true; }
+        }
+        false
+    }
+}
+
+// Padded params buffer and how many values were given, as the set_*_params exports take them.
+fn params<const N: usize>(args: &[f32]) -> ([f32; N], usize) {
+    let mut buf = [0f32; N];
+    let copy_len = args.len().min(N);
+    buf[..copy_len].copy_from_slice(&args[..copy_len]);
+    (buf, copy_len)
+}
+
+// Integer export arguments ride in f32 args bit-for-bit, so ids and colors past 2^24 survive.
+fn int_arg(v: i32) -> f32 { f32::from_bits(v as u32) }
+fn arg_int(v: f32) -> i32 { v.to_bits() as i32 }
+
+fn int_args(ids: &[i32]) -> Vec<f32> { ids.iter().map(|&id| int_arg(id)).collect() }
+fn arg_ints(args: &[f32]) -> Vec<i32> { args.iter().map(|&v| arg_int(v)).collect() }
+
+// [len, ...bytes packed 4 per value]
+fn byte_args(bytes: &[u8]) -> Vec<f32> {
+    let mut out = vec![int_arg(bytes.len() as i32)];
+    out.extend(bytes.chunks(4).map(|c| {
+        let mut word = [0u8; 4];
+        word[..c.len()].copy_from_slice(c);
+        f32::from_bits(u32::from_le_bytes(word))
+    }));
+    out
+}
+
+fn arg_bytes(args: &[f32]) -> Vec<u8> {
+    let Some((&len, words)) = args.split_first() else { return Vec::new(); };
+    let mut out: Vec<u8> = words.iter().flat_map(|v| v.to_bits().to_le_bytes()).collect();
+    out.truncate(arg_int(len).max(0) as usize);
+    out
+}
+
+type Inputs = Vec<(Input, Vec<f32>)>;
+
+// Preamble, snapshot and inputs of a start_recording buffer; None unless it parses completely.
+fn read_recording(bytes: &[u8]) -> Option<(Inputs, &[u8], Inputs)> {
+    let mut r = StateReader { buf: bytes, pos: 0 };
+    if r.take(4)? != RECORDING_MAGIC || r.u32()? != RECORDING_VERSION { return None; }
+    let mut preamble = Vec::new();
+    for _ in 0..r.count(8)? {
+        let kind = Input::from_code(r.u32()?)?;
+        let args = r.f32_vec()?;
+        preamble.push((kind, args));
+    }
+    let len = r.u32()? as usize;
+    let snapshot = r.take(len)?;
+    SavedState::read(snapshot)?;
+    let mut inputs = Vec::new();
+    let mut frames = 0u32;
+    while r.pos < bytes.len() {
+        let kind = Input::from_code(r.u32()?).filter(|&k| k != Input::Clock)?;
+        if r.u32()? != frames { return None; }
+        if kind == Input::Frame { frames += 1; }
+        inputs.push((kind, r.f32_vec()?));
+    }
+    Some((preamble, snapshot, inputs))
+}
+
+impl Input {
+    const ALL: [Input; 63] = [
+        Input::Frame, Input::Upsert, Input::UpsertV2, Input::Pointers,
+        Input::Patch, Input::Remove, Input::ClearNodes, Input::Reset,
+        Input::View, Input::ViewParams, Input::Viewport, Input::AutoscrollParams, Input::CameraParams,
+        Input::CameraBounds, Input::Constraints, Input::TapParams, Input::ThrowParams, Input::SwipeParams,
+        Input::GuideParams, Input::ResizeParams, Input::ParticleParams, Input::TimestepParams,
+        Input::DropZones, Input::RemoveDropZone, Input::ClearDropZones, Input::Clock,
+        Input::SetParent, Input::Group, Input::Ungroup, Input::BringToFront, Input::SendToBack,
+        Input::Raise, Input::Lower, Input::SetZ,
+        Input::Select, Input::Deselect, Input::ToggleSelection, Input::ClearSelection,
+        Input::ZoomAt, Input::Pan, Input::ReleasePan, Input::ZoomToRect, Input::FitToNodes,
+        Input::Animate, Input::Spring, Input::CancelAnimations,
+        Input::StartPath, Input::AddPoint, Input::FinishPath, Input::RemovePath, Input::ClearPaths,
+        Input::SpawnParticles, Input::ClearParticles, Input::SeedParticles, Input::StoreImage,
+        Input::Undo, Input::Redo, Input::BeginGroup, Input::EndGroup,
+        Input::HistoryDepth, Input::PauseHistory, Input::ClearHistory, Input::LoadState,
+    ];
+
+    fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|&k| k as u32 == code)
+    }
+}
+
+impl StateWriter {
+    fn f32(&mut self, v: f32) { self.0.extend_from_slice(&v.to_le_bytes()); }
+    fn i32(&mut self, v: i32) { self.0.extend_from_slice(&v.to_le_bytes()); }
+    fn u32(&mut self, v: u32) { self.0.extend_from_slice(&v.to_le_bytes()); }
+}
+
+impl<'a> StateReader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|&end| end <= self.buf.len())?;
+        let out = &self.buf[self.pos..end];
+        self.pos = end;
+        Some(out)
+    }
+
+    fn word(&mut self) -> Option<[u8; 4]> { self.take(4)?.try_into().ok() }
+    fn f32(&mut self) -> Option<f32> { self.word().map(f32::from_le_bytes) }
+    fn i32(&mut self) -> Option<i32> { self.word().map(i32::from_le_bytes) }
+    fn u32(&mut self) -> Option<u32> { self.word().map(u32::from_le_bytes) }
+
+    fn f32_vec(&mut self) -> Option<Vec<f32>> {
+        let n = self.count(4)?;
+        (0..n).map(|_| self.f32()).collect()
+    }
+
+    fn f32s<const N: usize>(&mut self) -> Option<[f32; N]> {
+        let mut out = [0.0; N];
+        for v in &mut out { *v = self.f32()?; }
+        Some(out)
+    }
+
+    // Item count, rejected when the rest of the buffer can't hold that many `min_size`-byte items.
+    fn count(&mut self, min_size: usize) -> Option<usize> {
+        let n = self.u32()? as usize;
+        (n.checked_mul(min_size)? <= self.buf.len() - self.pos).then_some(n)
+    }
+}
+
+impl SavedState {
+    fn read(bytes: &[u8]) -> Option<Self> {
+        let mut r = StateReader { buf: bytes, pos: 0 };
+        if r.take(4)? != STATE_MAGIC || r.u32()? != STATE_VERSION { return None; }
+        let view = r.f32s()?;
+        let constraints = r.f32s()?;
+        let particle_params = r.f32s()?;
+        let mut nodes = Vec::new();
+        let mut ids = HashSet::new();
+        for _ in 0..r.count(60)? {
+            let id = r.i32()?;
+            let [x, y, w, h, vx, vy] = r.f32s()?;
+            let flags = r.u32()?;
+            let [angle, scale_x, scale_y, pivot_x, pivot_y] = r.f32s()?;
+            let z = r.i32()?;
+            let parent = r.i32()?;
+            if !ids.insert(id) { return None; }
+            let parent = if parent < 0 { None } else { Some(parent) };
+            nodes.push((id, NodeState { x, y, w, h, flags, angle, scale_x, scale_y, pivot_x, pivot_y, z, parent }, vx, vy));
+        }
+        let mut paths = Vec::new();
+        for _ in 0..r.count(17)? {
+            let id = r.i32()?;
+            let color = r.u32()?;
+            let width = r.f32()?;
+            let closed = r.take(1)?[0] != 0;
+            let mut points = Vec::new();
+            for _ in 0..r.count(16)? { points.extend_from_slice(&r.f32s::<4>()?); }
+            paths.push(DrawPath { id, points, color, width, closed });
+        }
+        let mut particles = Vec::new();
+        for _ in 0..r.count(24)? {
+            let [x, y, vx, vy, pr, life] = r.f32s()?;
+            particles.push(Particle { x, y, vx, vy, r: pr, life });
+        }
+        let mut images = Vec::new();
+        for _ in 0..r.count(12)? {
+            let id = r.i32()?;
+            let (w, h) = (r.u32()?, r.u32()?);
+            let len = (w as usize).checked_mul(h as usize)?.checked_mul(4)?;
+            let data = r.take(len)?.to_vec();
+            images.push((id, Image { w, h, data }));
+        }
+        // Trailing bytes mean a different layout
+        (r.pos == bytes.len()).then_some(SavedState { view, constraints, particle_params, nodes, paths, particles, images })
+    }
+}
+
+impl Node {
+    fn pose(&self) -> Pose {
+        let l = &self.local;
+        [self.x, self.y, self.angle, self.scale_x, self.scale_y, self.w, self.h, l.x, l.y, l.angle, l.scale_x, l.scale_y]
+    }
+
+    // Idle, untransformed node at the origin.
+    fn new(id: i32) -> Self {
+        Node {
+            id,
+            x: 0.0, y: 0.0,
+            w: 0.0, h: 0.0,
+            vx: 0.0, vy: 0.0,
+            flags: 0,
+            angle: 0.0, scale_x: 1.0, scale_y: 1.0, pivot_x: 0.5, pivot_y: 0.5,
+            z: 0,
+            parent: None,
+            local: Local { x: 0.0, y: 0.0, angle: 0.0, scale_x: 1.0, scale_y: 1.0 },
+            local_dirty: true,
+            grabbing: false,
+            grab_pointer: -1,
+            grab_dx: 0.0,
+            grab_dy: 0.0,
+            group_leader: None,
+            pinch: None,
+            resize: None,
+            drop_zone: None,
+            last_tap_time: -1000.0,
+            drag_x: 0.0,
+            drag_y: 0.0,
+            tap_count: 0,
+            tap_x: 0.0,
+            tap_y: 0.0,
+            single_pending: false,
+            single_emit_time: 0.0,
+        }
+    }
+
+    fn state(&self) -> NodeState {
+        NodeState {
+            x: self.x, y: self.y, w: self.w, h: self.h,
+            flags: self.flags,
+            angle: self.angle, scale_x: self.scale_x, scale_y: self.scale_y,
+            pivot_x: self.pivot_x, pivot_y: self.pivot_y,
+            z: self.z,
+            parent: self.parent,
+        }
+    }
+
+    fn restore(&mut self, s: &NodeState) {
+        self.x = s.x; self.y = s.y; self.w = s.w; self.h = s.h;
+        self.vx = 0.0; self.vy = 0.0;
+        self.flags = s.flags;
+        self.angle = s.angle; self.scale_x = s.scale_x; self.scale_y = s.scale_y;
+        self.pivot_x = s.pivot_x; self.pivot_y = s.pivot_y;
+        self.z = s.z;
+        self.parent = s.parent;
+        self.local_dirty = true;
+    }
+
+    fn pivot_world(&self) -> (f32, f32) {
+        (self.x + self.pivot_x * self.w, self.y + self.pivot_y * self.h)
+    }
+
+    // Unrotated, unscaled node space (origin at the top-left) -> world point.
+    fn to_world(&self, lx: f32, ly: f32) -> (f32, f32) {
+        let (px, py) = self.pivot_world();
+        let (ox, oy) = rotate((lx - self.pivot_x * self.w) * self.scale_x, (ly - self.pivot_y * self.h) * self.scale_y, self.angle);
+        (px + ox, py + oy)
+    }
+
+    // Transform of `child` relative to this node's box.
+    fn local_of(&self, child: &Node) -> Local {
+        let (cx, cy) = child.pivot_world();
+        let (lx, ly) = self.to_local(cx, cy);
+        Local {
+            x: lx - child.pivot_x * child.w,
+            y: ly - child.pivot_y * child.h,
+            angle: child.angle - self.angle,
+            scale_x: child.scale_x / self.scale_x.max(1e-4),
+            scale_y: child.scale_y / self.scale_y.max(1e-4),
+        }
+    }
+
+    // Set `child`'s world transform from its local one.
+    fn place_child(&self, child: &mut Node) {
+        let l = child.local;
+        let (px, py) = self.to_world(l.x + child.pivot_x * child.w, l.y + child.pivot_y * child.h);
+        child.angle = self.angle + l.angle;
+        child.scale_x = self.scale_x * l.scale_x;
+        child.scale_y = self.scale_y * l.scale_y;
+        child.x = px - child.pivot_x * child.w;
+        child.y = py - child.pivot_y * child.h;
+    }
+
+    // World point -> unrotated, unscaled node space (origin at the top-left).
+    fn to_local(&self, x: f32, y: f32) -> (f32, f32) {
+        let (px, py) = self.pivot_world();
+        let (lx, ly) = rotate(x - px, y - py, -self.angle);
+        (lx / self.scale_x.max(1e-4) + self.pivot_x * self.w, ly / self.scale_y.max(1e-4) + self.pivot_y * self.h)
+    }
+
+    // Resize handle at the world point: within `reach` (world px) of an edge of the box.
+    fn handle_at(&self, x: f32, y: f32, reach: f32) -> Option<usize> {
+        let (lx, ly) = self.to_local(x, y);
+        let (rx, ry) = (reach / self.scale_x.max(1e-4), reach / self.scale_y.max(1e-4));
+        if lx < -rx || lx > self.w + rx || ly < -ry || ly > self.h + ry { return None; }
+        let ex = if lx <= rx { -1 } else if lx >= self.w - rx { 1 } else { 0 };
+        let ey = if ly <= ry { -1 } else if ly >= self.h - ry { 1 } else { 0 };
+        HANDLES.iter().position(|&h| h == (ex, ey))
+    }
+
+    // Shift the node so its bounding box stays inside the bounds rect (right/bottom may be infinite).
+    fn clamp_to(&mut self, left: f32, top: f32, right: f32, bottom: f32) {
+        let (dx, dy) = clamp_offset(self.aabb(), left, top, right, bottom);
+        self.x += dx;
+        self.y += dy;
+    }
+
+    // Point-in-node test honoring rotation and scale about the pivot.
+    fn contains(&self, x: f32, y: f32) -> bool {
+        let (lx, ly) = self.to_local(x, y);
+        lx >= 0.0 && lx <= self.w && ly >= 0.0 && ly <= self.h
+    }
+
+    // World-space bounding box of the transformed rect: (left, top, right, bottom).
+    fn aabb(&self) -> (f32, f32, f32, f32) {
+        let (px, py) = self.pivot_world();
+        let (ax, ay) = (self.pivot_x * self.w, self.pivot_y * self.h);
+        let (mut l, mut t, mut r, mut b) = (f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for (cx, cy) in [(0.0, 0.0), (self.w, 0.0), (0.0, self.h), (self.w, self.h)] {
+            let (ox, oy) = rotate((cx - ax) * self.scale_x, (cy - ay) * self.scale_y, self.angle);
+            l = l.min(px + ox); r = r.max(px + ox);
+            t = t.min(py + oy); b = b.max(py + oy);
+        }
+        (l, t, r, b)
+    }
+}
+
+impl EventKind {
+    const ALL: [EventKind; 18] = [
+        EventKind::DragStart, EventKind::DragEnd, EventKind::DragMove,
+        EventKind::Tap, EventKind::DoubleTap, EventKind::LongPress, EventKind::MultiTap, EventKind::Swipe,
+        EventKind::PinchStart, EventKind::PinchEnd,
+        EventKind::CollisionStart, EventKind::CollisionEnd,
+        EventKind::ResizeStart, EventKind::ResizeEnd,
+        EventKind::DropEnter, EventKind::DropLeave, EventKind::Drop,
+        EventKind::AnimEnd,
+    ];
+
+    // Name, meaning of [a, b, data] and of the float payload ("" = unused).
+    fn describe(self) -> (&'static str, [&'static str; 3], [&'static str; 4]) {
+        match self {
+            EventKind::DragStart => ("drag_start", ["nodeId", "", ""], ["nodeX", "nodeY", "pointerX", "pointerY"]),
+            EventKind::DragEnd => ("drag_end", ["nodeId", "", ""], ["nodeX", "nodeY", "releaseVx", "releaseVy"]),
+            EventKind::DragMove => ("drag_move", ["nodeId", "pointerId", ""], ["nodeX", "nodeY", "dx", "dy"]),
+            EventKind::Tap => ("tap", ["nodeId", "count", ""], ["x", "y", "", ""]),
+            EventKind::DoubleTap => ("double_tap", ["nodeId", "count", ""], ["x", "y", "", ""]),
+            EventKind::LongPress => ("long_press", ["nodeId", "pointerId", ""], ["x", "y", "", ""]),
+            EventKind::MultiTap => ("multi_tap", ["nodeId", "count", ""], ["x", "y", "", ""]),
+            // direction: 0 = right, 1 = down, 2 = left, 3 = up; speed in world px/s; nodeId -1 = empty space
+            EventKind::Swipe => ("swipe", ["nodeId", "direction", "speed"], ["vx", "vy", "", ""]),
+            EventKind::PinchStart => ("pinch_start", ["nodeId", "pointerA", "pointerB"], ["", "", "", ""]),
+            EventKind::PinchEnd => ("pinch_end", ["nodeId", "", ""], ["", "", "", ""]),
+            // a < b
+            EventKind::CollisionStart => ("collision_start", ["nodeId", "otherNodeId", ""], ["", "", "", ""]),
+            EventKind::CollisionEnd => ("collision_end", ["nodeId", "otherNodeId", ""], ["", "", "", ""]),
+            // handle: 0=nw, 1=n, 2=ne, 3=e, 4=se, 5=s, 6=sw, 7=w
+            EventKind::ResizeStart => ("resize_start", ["nodeId", "handle", ""], ["x", "y", "w", "h"]),
+            EventKind::ResizeEnd => ("resize_end", ["nodeId", "handle", ""], ["x", "y", "w", "h"]),
+            // A drop ends the hover; no drop_leave follows it
+            EventKind::DropEnter => ("drop_enter", ["nodeId", "zoneId", ""], ["", "", "", ""]),
+            EventKind::DropLeave => ("drop_leave", ["nodeId", "zoneId", ""], ["", "", "", ""]),
+            EventKind::Drop => ("drop", ["nodeId", "zoneId", ""], ["nodeX", "nodeY", "pointerX", "pointerY"]),
+            // property: 0=x, 1=y, 2=w, 3=h, 4=angle, 5=scale; cancelled = 1 when a grab or newer animation took over
+            EventKind::AnimEnd => ("anim_end", ["nodeId", "property", "cancelled"], ["value", "", "", ""]),
+        }
+    }
+}
+
+impl AnimProp {
+    fn from_code(code: u32) -> Option<Self> {
+        Some(match code {
+            0 => AnimProp::X,
+            1 => AnimProp::Y,
+            2 => AnimProp::W,
+            3 => AnimProp::H,
+            4 => AnimProp::Angle,
+            5 => AnimProp::Scale,
+            _ => return None,
+        })
+    }
+
+    fn get(self, n: &Node) -> f32 {
+        match self {
+            AnimProp::X => n.x,
+            AnimProp::Y => n.y,
+            AnimProp::W => n.w,
+            AnimProp::H => n.h,
+            AnimProp::Angle => n.angle,
+            AnimProp::Scale => n.scale_x,
+        }
+    }
+
+    fn set(self, n: &mut Node, v: f32) {
+        match self {
+            AnimProp::X => n.x = v,
+            AnimProp::Y => n.y = v,
+            AnimProp::W => n.w = v.max(0.0),
+            AnimProp::H => n.h = v.max(0.0),
+            AnimProp::Angle => n.angle = v,
+            AnimProp::Scale => { n.scale_x = v; n.scale_y = v; }
+        }
+        // Children keep the animated world values; their local transform follows
+        n.local_dirty = true;
+    }
+}
+
+impl Events {
+    fn push(&mut self, kind: EventKind, args: [i32; 3]) {
+        self.push_with(kind, args, [0.0; 4]);
+    }
+
+    fn push_with(&mut self, kind: EventKind, [a, b, data]: [i32; 3], payload: [f32; 4]) {
+        self.ints.extend_from_slice(&[kind as i32, a, b, data]);
+        self.floats.extend_from_slice(&payload);
+    }
+
+    // Drain into the output layout: a [0, version, stride, count] header record first.
+    fn take_frame(&mut self) -> (Vec<i32>, Vec<f32>) {
+        let count = (self.ints.len() / 4) as i32;
+        let mut ints = vec![0, EVENT_SCHEMA_VERSION, 4, count];
+        let mut floats = vec![0.0; 4];
+        ints.append(&mut self.ints);
+        floats.append(&mut self.floats);
+        (ints, floats)
+    }
+
+    fn clear(&mut self) {
+        self.ints.clear();
+        self.floats.clear();
+    }
+}
+
+impl Samples {
+    // Append a sample, folding samples from the same engine time and dropping stale ones.
+    fn record(&mut self, t: f32, x: f32, y: f32, window: f32) {
+        match self.0.last_mut() {
+            Some(last) if last.0 == t => { last.1 = x; last.2 = y; }
+            _ => self.0.push((t, x, y)),
+        }
+        self.0.retain(|s| t - s.0 <= window);
+    }
+
+    // Move every sample by the same offset (a change of reference frame).
+    fn shift(&mut self, dx: f32, dy: f32) {
+        for s in &mut self.0 { s.1 += dx; s.2 += dy; }
+    }
+
+    // Average velocity over the samples inside the window ending at `now`.
+    fn velocity(&self, now: f32, window: f32) -> (f32, f32) {
+        let mut recent = self.0.iter().filter(|s| now - s.0 <= window);
+        let Some(&(t0, x0, y0)) = recent.next() else { return (0.0, 0.0); };
+        let Some(&(t1, x1, y1)) = recent.next_back() else { return (0.0, 0.0); };
+        let dt = t1 - t0;
+        if dt <= 1e-4 { return (0.0, 0.0); }
+        ((x1 - x0) / dt, (y1 - y0) / dt)
+    }
+}
+
+impl History {
+    // Keep what a node looked like before its first edit in the open change.
+    fn touch_node(&mut self, id: i32, n: Option<&Node>) {
+        if self.paused { return; }
+        if let Some((nodes, _)) = &mut self.base { nodes.entry(id).or_insert_with(|| n.map(Node::state)); }
+    }
+
+    fn touch_path(&mut self, id: i32, p: Option<&DrawPath>) {
+        if self.paused { return; }
+        if let Some((_, paths)) = &mut self.base { paths.entry(id).or_insert_with(|| p.cloned()); }
+    }
+
+    // For edits that may leave a node as it was: state to hand to `touch_changed` afterwards.
+    fn before(&self, n: &Node) -> Option<NodeState> {
+        let (nodes, _) = self.base.as_ref().filter(|_| !self.paused)?;
+        (!nodes.contains_key(&n.id)).then(|| n.state())
+    }
+
+    fn touch_changed(&mut self, n: &Node, before: Option<NodeState>) {
+        let Some(s) = before else { return; };
+        if n.state() == s { return; }
+        if let Some((nodes, _)) = &mut self.base { nodes.insert(n.id, Some(s)); }
+    }
+}
+
+impl SpatialGrid {
+    // Nodes touching more cells than this go to the always-checked list.
+    const MAX_SPAN_CELLS: i64 = 1024;
+
+    fn span(&self, l: f32, t: f32, r: f32, b: f32) -> Option<(i32, i32, i32, i32)> {
+        if !(l.is_finite() && t.is_finite() && r.is_finite() && b.is_finite()) { return None; }
+        let c = self.cell;
+        let (x0, y0) = ((l / c).floor() as i32, (t / c).floor() as i32);
+        let (x1, y1) = ((r / c).floor() as i32, (b / c).floor() as i32);
+        let cells = (x1 as i64 - x0 as i64 + 1) * (y1 as i64 - y0 as i64 + 1);
+        if cells > Self::MAX_SPAN_CELLS { None } else { Some((x0, y0, x1, y1)) }
+    }
+
+    // Insert or move a node; a no-op when its cell span is unchanged.
+    fn insert(&mut self, id: i32, (l, t, r, b): (f32, f32, f32, f32)) {
+        let span = self.span(l, t, r, b);
+        if self.spans.get(&id) == Some(&span) { return; }
+        self.remove(id);
+        match span {
+            Some((x0, y0, x1, y1)) => {
+                for cy in y0..=y1 {
+                    for cx in x0..=x1 { self.cells.entry((cx, cy)).or_default().push(id); }
+                }
+            }
+            None => self.large.push(id),
+        }
+        self.spans.insert(id, span);
+    }
+
+    fn remove(&mut self, id: i32) {
+        match self.spans.remove(&id) {
+            Some(Some((x0, y0, x1, y1))) => {
+                for cy in y0..=y1 {
+                    for cx in x0..=x1 {
+                        if let Some(bucket) = self.cells.get_mut(&(cx, cy)) {
+                            bucket.retain(|&v| v != id);
+                            if bucket.is_empty() { self.cells.remove(&(cx, cy)); }
+                        }
+                    }
+                }
+            }
+            Some(None) => self.large.retain(|&v| v != id),
+            None => {}
+        }
+    }
+
+    fn clear(&mut self) {
+        self.cells.clear();
+        self.spans.clear();
+        self.large.clear();
+    }
+
+    // Candidate ids (deduplicated) for a world rect; callers do the exact test.
+    fn query(&self, l: f32, t: f32, r: f32, b: f32, out: &mut Vec<i32>) {
+        out.extend_from_slice(&self.large);
+        match self.span(l, t, r, b) {
+            Some((x0, y0, x1, y1)) => {
+                for cy in y0..=y1 {
+                    for cx in x0..=x1 {
+                        if let Some(bucket) = self.cells.get(&(cx, cy)) { out.extend_from_slice(bucket); }
+                    }
+                }
+            }
+            // Query too wide to walk cell by cell: every indexed node is a candidate
+            None => out.extend(self.spans.keys().copied()),
+        }
+        out.sort_unstable();
+        out.dedup();
+    }
+}
+
+// Offset that moves a box inside the bounds rect (right/bottom may be infinite).
+fn clamp_offset((l, t, r, b): (f32, f32, f32, f32), left: f32, top: f32, right: f32, bottom: f32) -> (f32, f32) {
+    let max_x = if right.is_finite() { (right - left - (r - l)).max(0.0) } else { f32::INFINITY };
+    let max_y = if bottom.is_finite() { (bottom - top - (b - t)).max(0.0) } else { f32::INFINITY };
+    (l.max(left).min(left + max_x) - l, t.max(top).min(top + max_y) - t)
+}
+
+// Event for a tap sequence of `count` taps: tap, double_tap or multi_tap.
+fn tap_event(id: i32, count: u32) -> (EventKind, [i32; 3]) {
+    let kind = match count { 0 | 1 => EventKind::Tap, 2 => EventKind::DoubleTap, _ => EventKind::MultiTap };
+    (kind, [id, count.max(1) as i32, 0])
+}
+
+// Cubic ease-in-out over k in [0, 1].
+fn ease_in_out(k: f32) -> f32 {
+    if k < 0.5 { 4.0 * k * k * k } else { 1.0 - (2.0 - 2.0 * k).powi(3) * 0.5 }
+}
+
+// Easing curves by code: 0 = linear, 1 = ease-in-out, 2 = ease-out, 3 = ease-in (cubic).
+fn ease(curve: u32, k: f32) -> f32 {
+    match curve {
+        1 => ease_in_out(k),
+        2 => 1.0 - (1.0 - k).powi(3),
+        3 => k * k * k,
+        _ => k,
+    }
+}
+
+fn rotate(x: f32, y: f32, angle: f32) -> (f32, f32) {
+    if angle == 0.0 { return (x, y); }
+    let (s, c) = angle.sin_cos();
+    (x * c - y * s, x * s + y * c)
+}
+
+thread_local! {
+    static ENGINE: RefCell<Option<Engine>> = const { RefCell::new(None) };
+}
+
+#[wasm_bindgen]
+pub fn init(capacity: u32) {
+    ENGINE.with(|e| {
+        *e.borrow_mut() = Some(Engine::new(capacity as usize));
+    });
+}
+
+#[wasm_bindgen]
+pub fn reset() {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::Reset, &[]);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_view(scale: f32) {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::View, &[scale]);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_view_params(scale: f32, pan_x: f32, pan_y: f32, pixel_ratio: f32) {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::ViewParams, &[scale, pan_x, pan_y, pixel_ratio]);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_viewport(width: f32, height: f32) {
+    // CSS px, same units as pan; 0 = unknown (disables autoscroll)
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::Viewport, &[width, height]);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_autoscroll_params(params: Float32Array) {
+    // [margin (CSS px, 0 = off), max_speed (CSS px/s at the edge)]
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::AutoscrollParams, &params.to_vec());
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_camera_params(params: Float32Array) {
+    // [min_zoom, max_zoom, pan_damping (fraction of fling velocity kept per second)]
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::CameraParams, &params.to_vec());
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_camera_bounds(left: f32, top: f32, right: f32, bottom: f32) {
+    // World rect the viewport is kept inside
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::CameraBounds, &[left, top, right, bottom]);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn clear_camera_bounds() {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::CameraBounds, &[0.0, 0.0, -1.0, -1.0]);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn zoom_at(factor: f32, sx: f32, sy: f32) {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::ZoomAt, &[factor, sx, sy]);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn pan_camera(dx: f32, dy: f32) {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::Pan, &[dx, dy]);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn release_camera_pan() {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::ReleasePan, &[]);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn zoom_to_rect(l: f32, t: f32, r: f32, b: f32, padding: f32, duration: f32) -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() { eng.input(Input::ZoomToRect, &[l, t, r, b, padding, duration]) != 0 } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn fit_to_nodes(ids: Int32Array, padding: f32, duration: f32) -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            let ids = ids.to_vec();
+            eng.log(Input::FitToNodes, || [vec![padding, duration], int_args(&ids)].concat());
+            eng.fit_to_nodes(&ids, padding, duration)
+        } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn set_constraints(params: Float32Array) {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::Constraints, &params.to_vec());
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_tap_params(params: Float32Array) {
+    // [tap_max_s, move_thresh_px, double_s, single_delay_s, long_press_s (0 = off), max_taps]
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::TapParams, &params.to_vec());
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_throw_params(params: Float32Array) {
+    // [window_s, max_speed]
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::ThrowParams, &params.to_vec());
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_swipe_params(params: Float32Array) {
+    // [min_speed (world px/s, 0 = off), min_distance (world px)]
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::SwipeParams, &params.to_vec());
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_guide_params(params: Float32Array) {
+    // [threshold (world px, 0 = off), snap_centers (0/1)]
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::GuideParams, &params.to_vec());
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_resize_params(params: Float32Array) {
+    // [handle_px (screen, 0 = off), min_w, min_h, max_w, max_h (0 = unbounded)]
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::ResizeParams, &params.to_vec());
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_timestep_params(params: Float32Array) {
+    // [rate_hz (0 = step by frame dt), max_substeps per frame]
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::TimestepParams, &params.to_vec());
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn upsert_nodes(nodes: Float32Array) {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::Upsert, &nodes.to_vec());
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn upsert_nodes_v2(nodes: Float32Array) {
+    // [id, x, y, w, h, vx, vy, flags, angle, scaleX, scaleY, pivotX, pivotY] * N
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::UpsertV2, &nodes.to_vec());
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn animate_node(id: i32, prop: u32, target: f32, duration: f32, easing: u32) -> bool {
+    // prop: 0=x, 1=y, 2=w, 3=h, 4=angle, 5=scale; easing: 0=linear, 1=ease-in-out, 2=ease-out, 3=ease-in
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.log(Input::Animate, || vec![int_arg(id), int_arg(prop as i32), target, duration, int_arg(easing as i32)]);
+            let Some(prop) = AnimProp::from_code(prop) else { return false; };
+            eng.animate(id, prop, target, duration, easing, None)
+        } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn spring_node(id: i32, prop: u32, target: f32, stiffness: f32, damping: f32) -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.log(Input::Spring, || vec![int_arg(id), int_arg(prop as i32), target, stiffness, damping]);
+            let Some(prop) = AnimProp::from_code(prop) else { return false; };
+            eng.animate(id, prop, target, 0.0, 0, Some((stiffness, damping)))
+        } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn cancel_node_animations(id: i32) -> u32 {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.log(Input::CancelAnimations, || vec![int_arg(id)]);
+            eng.cancel_tweens(id)
+        } else { 0 }
+    })
+}
+
+#[wasm_bindgen]
+pub fn patch_nodes(fields: u32, data: Float32Array) -> u32 {
+    // fields: bit 0 = x, y; 1 = w, h; 2 = vx, vy; 3 = flags; 4 = angle, scaleX, scaleY; 5 = pivotX, pivotY
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            let data = data.to_vec();
+            eng.log(Input::Patch, || [&[int_arg(fields as i32)], &data[..]].concat());
+            eng.record(|eng| eng.patch_nodes(fields, &data))
+        } else { 0 }
+    })
+}
+
+#[wasm_bindgen]
+pub fn remove_nodes(ids: Int32Array) -> u32 {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            let ids = ids.to_vec();
+            eng.log(Input::Remove, || int_args(&ids));
+            eng.record(|eng| eng.remove_nodes(&ids))
+        } else { 0 }
+    })
+}
+
+#[wasm_bindgen]
+pub fn clear_nodes() {
+    ENGINE.with(|e| {
         if let Some(ref mut eng) = *e.borrow_mut() {
-            eng.scale = if scale > 0.0 { scale } else { 1.0 };
-            eng.pan_x = pan_x;
-            eng.pan_y = pan_y;
-            eng.pixel_ratio = if pixel_ratio > 0.0 { pixel_ratio } else { 1.0 };
+            eng.input(Input::ClearNodes, &[]);
         }
     });
 }
 
 #[wasm_bindgen]
-pub fn set_constraints(params: Float32Array) {
+pub fn upsert_drop_zones(zones: Float32Array) {
+    // [id, x, y, w, h, acceptMask, mode (0 = pointer, 1 = overlap)] * Z in world px
     ENGINE.with(|e| {
         if let Some(ref mut eng) = *e.borrow_mut() {
-            let mut buf = [0f32; 8];
-            let len = params.length() as usize;
-            let copy_len = len.min(8);
-            for i in 0..copy_len { buf[i] = params.get_index(i as u32); }
-            if copy_len >= 4 {
-                eng.left = buf[0]; eng.top = buf[1]; eng.right = buf[2]; eng.bottom = buf[3];
-            }
-            if copy_len >= 6 { eng.grid_x = buf[4].max(0.0); eng.grid_y = buf[5].max(0.0); }
-            if copy_len >= 8 { eng.inertia = buf[6].max(0.0); eng.damping = buf[7].clamp(0.0, 1.0); }
+            eng.input(Input::DropZones, &zones.to_vec());
         }
     });
 }
 
 #[wasm_bindgen]
-pub fn set_tap_params(params: Float32Array) {
-    // [tap_max_s, move_thresh_px, double_s, single_delay_s]
+pub fn remove_drop_zone(id: i32) -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.log(Input::RemoveDropZone, || vec![int_arg(id)]);
+            eng.remove_drop_zone(id)
+        } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn clear_drop_zones() {
     ENGINE.with(|e| {
         if let Some(ref mut eng) = *e.borrow_mut() {
-            let mut buf = [0f32; 4];
-            let len = params.length() as usize;
-            let copy_len = len.min(4);
-            for i in 0..copy_len { buf[i] = params.get_index(i as u32); }
-            if copy_len >= 1 { eng.tap_max_s = buf[0].max(0.0); }
-            if copy_len >= 2 { eng.move_thresh_px = buf[1].max(0.0); }
-            if copy_len >= 3 { eng.double_s = buf[2].max(0.0); }
-            if copy_len >= 4 { eng.single_delay_s = buf[3].max(0.0); }
+            eng.input(Input::ClearDropZones, &[]);
         }
     });
 }
 
 #[wasm_bindgen]
-pub fn upsert_nodes(nodes: Float32Array) {
+pub fn hit_test(x: f32, y: f32, screen: bool) -> i32 {
+    // Topmost node id under the point, or -1; screen=true takes screen px like apply_pointers
+    ENGINE.with(|e| {
+        if let Some(ref eng) = *e.borrow() {
+            let (wx, wy) = if screen { eng.screen_to_world(x, y) } else { (x, y) };
+            eng.hit_test_world(wx, wy).unwrap_or(-1)
+        } else { -1 }
+    })
+}
+
+// Point conversions using the current view (same math as apply_pointers); returns [x, y].
+#[wasm_bindgen]
+pub fn screen_to_world(x: f32, y: f32) -> Float32Array {
+    map_points(&[x, y], true)
+}
+
+#[wasm_bindgen]
+pub fn world_to_screen(x: f32, y: f32) -> Float32Array {
+    map_points(&[x, y], false)
+}
+
+// Batch versions over [x, y] * N.
+#[wasm_bindgen]
+pub fn screen_to_world_batch(points: Float32Array) -> Float32Array {
+    map_points(&points.to_vec(), true)
+}
+
+#[wasm_bindgen]
+pub fn world_to_screen_batch(points: Float32Array) -> Float32Array {
+    map_points(&points.to_vec(), false)
+}
+
+fn map_points(pts: &[f32], to_world: bool) -> Float32Array {
+    // Before init the view is the identity
+    let out = ENGINE.with(|e| match *e.borrow() {
+        Some(ref eng) => eng.map_points(pts, to_world),
+        None => pts[..pts.len() & !1].to_vec(),
+    });
+    let arr = Float32Array::new_with_length(out.len() as u32);
+    arr.copy_from(&out[..]);
+    arr
+}
+
+#[wasm_bindgen]
+pub fn query_rect(l: f32, t: f32, r: f32, b: f32, screen: bool) -> Int32Array {
+    // Ids of nodes whose bounding box overlaps the rect, in stacking order
+    let mut out = Int32Array::new_with_length(0);
+    ENGINE.with(|e| {
+        if let Some(ref eng) = *e.borrow() {
+            let ((l, t), (r, b)) = if screen {
+                (eng.screen_to_world(l, t), eng.screen_to_world(r, b))
+            } else { ((l, t), (r, b)) };
+            let ids = eng.query_rect_world(l, t, r, b);
+            let arr = Int32Array::new_with_length(ids.len() as u32);
+            arr.copy_from(&ids[..]);
+            out = arr;
+        }
+    });
+    out
+}
+
+#[wasm_bindgen]
+pub fn set_parent(child: i32, parent: i32) -> bool {
+    // parent < 0 detaches; world position is preserved either way
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.log(Input::SetParent, || vec![int_arg(child), int_arg(parent)]);
+            eng.record(|eng| eng.set_parent(child, (parent >= 0).then_some(parent)))
+        } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn group_nodes(parent: i32, ids: Int32Array) -> u32 {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            let ids = ids.to_vec();
+            eng.log(Input::Group, || [vec![int_arg(parent)], int_args(&ids)].concat());
+            eng.record(|eng| eng.group(parent, &ids))
+        } else { 0 }
+    })
+}
+
+#[wasm_bindgen]
+pub fn ungroup_nodes(parent: i32) -> u32 {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.log(Input::Ungroup, || vec![int_arg(parent)]);
+            eng.record(|eng| eng.ungroup(parent))
+        } else { 0 }
+    })
+}
+
+#[wasm_bindgen]
+pub fn bring_to_front(id: i32) -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.log(Input::BringToFront, || vec![int_arg(id)]);
+            eng.record(|eng| eng.bring_to_front(id))
+        } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn send_to_back(id: i32) -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.log(Input::SendToBack, || vec![int_arg(id)]);
+            eng.record(|eng| eng.send_to_back(id))
+        } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn raise_node(id: i32) -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.log(Input::Raise, || vec![int_arg(id)]);
+            eng.record(|eng| eng.shift_z(id, 1))
+        } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn lower_node(id: i32) -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.log(Input::Lower, || vec![int_arg(id)]);
+            eng.record(|eng| eng.shift_z(id, -1))
+        } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn set_z_index(id: i32, z: i32) -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.log(Input::SetZ, || vec![int_arg(id), int_arg(z)]);
+            eng.set_z_index(id, z)
+        } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn get_z_order() -> Int32Array {
+    // Node ids bottom to top
+    let mut out = Int32Array::new_with_length(0);
+    ENGINE.with(|e| {
+        if let Some(ref eng) = *e.borrow() {
+            let ids: Vec<i32> = eng.stacking().into_iter().map(|i| eng.nodes[i].id).collect();
+            let arr = Int32Array::new_with_length(ids.len() as u32);
+            arr.copy_from(&ids[..]);
+            out = arr;
+        }
+    });
+    out
+}
+
+#[wasm_bindgen]
+pub fn select_nodes(ids: Int32Array) {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            let ids = ids.to_vec();
+            eng.log(Input::Select, || int_args(&ids));
+            eng.select(&ids);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn deselect_nodes(ids: Int32Array) {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            let ids = ids.to_vec();
+            eng.log(Input::Deselect, || int_args(&ids));
+            eng.deselect(&ids);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn toggle_selection(ids: Int32Array) {
     ENGINE.with(|e| {
         if let Some(ref mut eng) = *e.borrow_mut() {
-            let mut buf = vec![0f32; nodes.length() as usize];
-            nodes.copy_to(&mut buf);
-            eng.upsert_nodes(&buf);
+            let ids = ids.to_vec();
+            eng.log(Input::ToggleSelection, || int_args(&ids));
+            eng.toggle_selection(&ids);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn clear_selection() {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::ClearSelection, &[]);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn get_selection() -> Int32Array {
+    let mut out = Int32Array::new_with_length(0);
+    ENGINE.with(|e| {
+        if let Some(ref eng) = *e.borrow() {
+            let ids: Vec<i32> = eng.selection.iter().copied().collect();
+            let arr = Int32Array::new_with_length(ids.len() as u32);
+            arr.copy_from(&ids[..]);
+            out = arr;
         }
     });
+    out
 }
 
 #[wasm_bindgen]
 pub fn apply_pointers(pointers: Float32Array) {
     ENGINE.with(|e| {
         if let Some(ref mut eng) = *e.borrow_mut() {
-            let mut buf = vec![0f32; pointers.length() as usize];
-            pointers.copy_to(&mut buf);
-            eng.apply_pointers(&buf);
+            eng.input(Input::Pointers, &pointers.to_vec());
         }
     });
 }
 
 #[wasm_bindgen]
 pub fn process_frame(dt: f32) -> JsValue {
-    let (transforms, particles, draw_paths, events) = ENGINE.with(|e| {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::Frame, &[dt]);
+        }
+    });
+    frame_output()
+}
+
+// Input recording and deterministic replay
+#[wasm_bindgen]
+pub fn start_recording() -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() { eng.start_recording() } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn stop_recording() -> Uint8Array {
+    let bytes = ENGINE.with(|e| match *e.borrow_mut() {
+        Some(ref mut eng) => eng.stop_recording(),
+        None => Vec::new(),
+    });
+    let arr = Uint8Array::new_with_length(bytes.len() as u32);
+    arr.copy_from(&bytes[..]);
+    arr
+}
+
+#[wasm_bindgen]
+pub fn is_recording() -> bool {
+    ENGINE.with(|e| e.borrow().as_ref().is_some_and(|eng| eng.recorder.is_some()))
+}
+
+#[wasm_bindgen]
+pub fn load_recording(bytes: Uint8Array) -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() { eng.load_recording(&bytes.to_vec()) } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn replay_frame() -> JsValue {
+    // Output of the next recorded process_frame, or null once the recording is used up
+    let stepped = ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() { eng.replay_frame() } else { false }
+    });
+    if stepped { frame_output() } else { JsValue::NULL }
+}
+
+// process_frame's output for the engine's current state; drains the frame's events.
+fn frame_output() -> JsValue {
+    let (transforms, sizes, locals, particles, draw_paths, guides, view, events, event_data) = ENGINE.with(|e| {
         let mut transforms = Float32Array::new_with_length(0);
+        let mut sizes = Float32Array::new_with_length(0);
+        let mut locals = Float32Array::new_with_length(0);
         let mut particles = Float32Array::new_with_length(0);
         let mut draw_paths = Float32Array::new_with_length(0);
+        let mut guides = Float32Array::new_with_length(0);
+        let mut view = Float32Array::new_with_length(0);
         let mut events = Int32Array::new_with_length(0);
+        let mut event_data = Float32Array::new_with_length(0);
         if let Some(ref mut eng) = *e.borrow_mut() {
-            eng.step(dt);
             transforms = eng.write_transforms();
+            sizes = eng.write_sizes();
+            locals = eng.write_locals();
             particles = eng.write_particles();
             draw_paths = eng.write_draw_paths();
-            // Drain events ring buffer
-            if !eng.events.is_empty() {
-                let len = eng.events.len() as u32;
-                let arr = Int32Array::new_with_length(len);
-                // Copy from Vec<i32> to Int32Array
-                for (i, v) in eng.events.iter().enumerate() { arr.set_index(i as u32, *v); }
-                events = arr;
-                eng.events.clear();
-            }
+            guides = eng.write_guides();
+            view = eng.write_view();
+            // Drain events ring buffer (header record even when empty)
+            let (ints, floats) = eng.events.take_frame();
+            events = Int32Array::new_with_length(ints.len() as u32);
+            events.copy_from(&ints[..]);
+            event_data = Float32Array::new_with_length(floats.len() as u32);
+            event_data.copy_from(&floats[..]);
         }
-        (transforms, particles, draw_paths, events)
+        (transforms, sizes, locals, particles, draw_paths, guides, view, events, event_data)
     });
 
     let obj = Object::new();
     Reflect::set(&obj, &JsValue::from_str("transforms"), &transforms).ok();
+    Reflect::set(&obj, &JsValue::from_str("sizes"), &sizes).ok();
+    Reflect::set(&obj, &JsValue::from_str("locals"), &locals).ok();
     Reflect::set(&obj, &JsValue::from_str("particles"), &particles).ok();
     Reflect::set(&obj, &JsValue::from_str("drawPaths"), &draw_paths).ok();
+    Reflect::set(&obj, &JsValue::from_str("guides"), &guides).ok();
+    Reflect::set(&obj, &JsValue::from_str("view"), &view).ok();
     Reflect::set(&obj, &JsValue::from_str("events"), &events).ok();
+    Reflect::set(&obj, &JsValue::from_str("eventData"), &event_data).ok();
+    JsValue::from(obj)
+}
+
+#[wasm_bindgen]
+pub fn event_schema() -> JsValue {
+    // { version, stride, header: [...], types: [{ code, name, fields: [a, b, data], payload: [f0..f3] }] }
+    let str_array = |names: &[&str]| {
+        let arr = js_sys::Array::new();
+        for n in names { arr.push(&JsValue::from_str(n)); }
+        arr
+    };
+    let types = js_sys::Array::new();
+    for kind in EventKind::ALL {
+        let (name, fields, payload) = kind.describe();
+        let t = Object::new();
+        Reflect::set(&t, &JsValue::from_str("code"), &JsValue::from(kind as i32)).ok();
+        Reflect::set(&t, &JsValue::from_str("name"), &JsValue::from_str(name)).ok();
+        Reflect::set(&t, &JsValue::from_str("fields"), &str_array(&fields)).ok();
+        Reflect::set(&t, &JsValue::from_str("payload"), &str_array(&payload)).ok();
+        types.push(&t);
+    }
+    let obj = Object::new();
+    Reflect::set(&obj, &JsValue::from_str("version"), &JsValue::from(EVENT_SCHEMA_VERSION)).ok();
+    Reflect::set(&obj, &JsValue::from_str("stride"), &JsValue::from(4)).ok();
+    Reflect::set(&obj, &JsValue::from_str("header"), &str_array(&["zero", "version", "stride", "count"])).ok();
+    Reflect::set(&obj, &JsValue::from_str("types"), &types).ok();
     JsValue::from(obj)
 }
 
 #[wasm_bindgen]
 pub fn spawn_particles(data: Float32Array) -> u32 {
     // data stride 6: [x, y, vx, vy, r, life]
-    let mut count = 0u32;
     ENGINE.with(|e| {
-        if let Some(ref mut eng) = *e.borrow_mut() {
-            let len = data.length() as usize;
-            if len == 0 { return; }
-            let mut buf = vec![0f32; len];
-            data.copy_to(&mut buf);
-            let stride = 6usize;
-            for chunk in buf.chunks(stride) {
-                if chunk.len() < stride { break; }
-                let p = Particle { x: chunk[0], y: chunk[1], vx: chunk[2], vy: chunk[3], r: chunk[4].max(0.1), life: chunk[5].max(0.0) };
-                if p.life > 0.0 { eng.particles.push(p); count += 1; }
-            }
-        }
-    });
-    count
+        if let Some(ref mut eng) = *e.borrow_mut() { eng.input(Input::SpawnParticles, &data.to_vec()) } else { 0 }
+    })
 }
 
 #[wasm_bindgen]
 pub fn clear_particles() {
     ENGINE.with(|e| {
-        if let Some(ref mut eng) = *e.borrow_mut() { eng.particles.clear(); }
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::ClearParticles, &[]);
+        }
     });
 }
 
@@ -529,13 +3963,8 @@ pub fn clear_particles() {
 pub fn seed_particles_for_bench(n: u32) {
     ENGINE.with(|e| {
         if let Some(ref mut eng) = *e.borrow_mut() {
-            eng.particles.clear();
-            let n = n as usize;
-            for i in 0..n {
-                let x = (i % 800) as f32;
-                let y = (i / 800) as f32;
-                eng.particles.push(Particle { x, y, vx: 10.0, vy: -5.0, r: 1.0, life: 10.0 });
-            }
+            eng.log(Input::SeedParticles, || vec![int_arg(n as i32)]);
+            eng.seed_particles(n as usize);
         }
     });
 }
@@ -545,13 +3974,7 @@ pub fn set_particle_params(params: Float32Array) {
     // [g_x, g_y, damping(0..1), restitution(0..1)]
     ENGINE.with(|e| {
         if let Some(ref mut eng) = *e.borrow_mut() {
-            let mut buf = [0f32; 4];
-            let len = params.length() as usize;
-            let copy_len = len.min(4);
-            for i in 0..copy_len { buf[i] = params.get_index(i as u32); }
-            if copy_len >= 2 { eng.g_x = buf[0]; eng.g_y = buf[1]; }
-            if copy_len >= 3 { eng.p_damping = buf[2].clamp(0.0, 1.0); }
-            if copy_len >= 4 { eng.restitution = buf[3].clamp(0.0, 1.0); }
+            eng.input(Input::ParticleParams, &params.to_vec());
         }
     });
 }
@@ -564,9 +3987,9 @@ pub fn store_image(id: i32, rgba: Uint8Array, w: u32, h: u32) -> bool {
             let expected = (w as usize).saturating_mul(h as usize).saturating_mul(4);
             if (rgba.length() as usize)  < expected { return false; }
             let mut buf = vec![0u8; expected];
-            rgba.copy_to(&mut buf[..]);
-            eng.images.insert(id, Image { w, h, data: buf });
-            true
+            rgba.subarray(0, expected as u32).copy_to(&mut buf[..]);
+            eng.log(Input::StoreImage, || [vec![int_arg(id), int_arg(w as i32), int_arg(h as i32)], byte_args(&buf)].concat());
+            eng.store_image(id, w, h, buf)
         } else { false }
     })
 }
@@ -577,18 +4000,7 @@ pub fn resize_image(id: i32, out_w: u32, out_h: u32) -> Uint8Array {
     ENGINE.with(|e| {
         if let Some(ref eng) = *e.borrow() {
             if let Some(img) = eng.images.get(&id) {
-                let sw = img.w.max(1); let sh = img.h.max(1);
-                let ow = out_w.max(1); let oh = out_h.max(1);
-                let mut dst = vec![0u8; (ow as usize) * (oh as usize) * 4];
-                for y in 0..oh {
-                    let sy = (y as u64 * sh as u64) / oh as u64;
-                    for x in 0..ow {
-                        let sx = (x as u64 * sw as u64) / ow as u64;
-                        let si = ((sy as usize) * (sw as usize) + (sx as usize)) * 4;
-                        let di = ((y as usize) * (ow as usize) + (x as usize)) * 4;
-                        dst[di..di+4].copy_from_slice(&img.data[si..si+4]);
-                    }
-                }
+                let dst = resize_nearest_rgba(&img.data, img.w, img.h, out_w, out_h);
                 let arr = Uint8Array::new_with_length(dst.len() as u32);
                 arr.copy_from(&dst[..]);
                 out = arr;
@@ -602,15 +4014,8 @@ pub fn resize_image(id: i32, out_w: u32, out_h: u32) -> Uint8Array {
 pub fn start_draw_path(id: i32, x: f32, y: f32, pressure: f32, color: u32, width: f32) -> bool {
     ENGINE.with(|e| {
         if let Some(ref mut eng) = *e.borrow_mut() {
-            let path = DrawPath {
-                id,
-                points: vec![x, y, pressure, eng.time],
-                color,
-                width,
-                closed: false,
-            };
-            eng.draw_paths.insert(id, path);
-            true
+            eng.log(Input::StartPath, || vec![int_arg(id), x, y, pressure, int_arg(color as i32), width]);
+            eng.start_path(id, x, y, pressure, color, width)
         } else { false }
     })
 }
@@ -619,42 +4024,148 @@ pub fn start_draw_path(id: i32, x: f32, y: f32, pressure: f32, color: u32, width
 pub fn add_draw_point(id: i32, x: f32, y: f32, pressure: f32) -> bool {
     ENGINE.with(|e| {
         if let Some(ref mut eng) = *e.borrow_mut() {
-            if let Some(path) = eng.draw_paths.get_mut(&id) {
-                path.points.extend_from_slice(&[x, y, pressure, eng.time]);
-                true
-            } else { false }
+            eng.log(Input::AddPoint, || vec![int_arg(id), x, y, pressure]);
+            eng.add_point(id, x, y, pressure)
+        } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn finish_draw_path(id: i32, closed: bool) -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.log(Input::FinishPath, || vec![int_arg(id), if closed { 1.0 } else { 0.0 }]);
+            eng.finish_path(id, closed)
         } else { false }
     })
 }
 
 #[wasm_bindgen]
-pub fn finish_draw_path(id: i32, closed: bool) -> bool {
+pub fn remove_draw_path(id: i32) -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.log(Input::RemovePath, || vec![int_arg(id)]);
+            eng.remove_path(id)
+        } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn clear_draw_paths() {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::ClearPaths, &[]);
+        }
+    });
+}
+
+// History: gestures, strokes and the edits above are recorded as undo steps
+#[wasm_bindgen]
+pub fn undo() -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() { eng.input(Input::Undo, &[]) != 0 } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn redo() -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() { eng.input(Input::Redo, &[]) != 0 } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn can_undo() -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref eng) = *e.borrow() { eng.can_undo() } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn can_redo() -> bool {
+    ENGINE.with(|e| {
+        if let Some(ref eng) = *e.borrow() { eng.can_redo() } else { false }
+    })
+}
+
+#[wasm_bindgen]
+pub fn begin_history_group() {
+    // Everything until the matching end_history_group becomes one undo step
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::BeginGroup, &[]);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn end_history_group() {
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::EndGroup, &[]);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_history_depth(depth: u32) {
+    // Max undo steps kept (0 = history off)
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.log(Input::HistoryDepth, || vec![int_arg(depth as i32)]);
+            eng.set_history_depth(depth as usize);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn pause_history(paused: bool) {
+    // Edits while paused are applied but never recorded (layout sync: mount, measure, unmount)
+    ENGINE.with(|e| {
+        if let Some(ref mut eng) = *e.borrow_mut() {
+            eng.input(Input::PauseHistory, &[if paused { 1.0 } else { 0.0 }]);
+        }
+    });
+}
+
+#[wasm_bindgen]
+pub fn clear_history() {
     ENGINE.with(|e| {
         if let Some(ref mut eng) = *e.borrow_mut() {
-            if let Some(path) = eng.draw_paths.get_mut(&id) {
-                path.closed = closed;
-                true
-            } else { false }
-        } else { false }
-    })
+            eng.input(Input::ClearHistory, &[]);
+        }
+    });
 }
 
+// Scene snapshot: nodes, draw paths, particles, images, view and constraints
 #[wasm_bindgen]
-pub fn remove_draw_path(id: i32) -> bool {
+pub fn save_state() -> Uint8Array {
+    let bytes = ENGINE.with(|e| match *e.borrow() {
+        Some(ref eng) => eng.save_state(),
+        None => Vec::new(),
+    });
+    let arr = Uint8Array::new_with_length(bytes.len() as u32);
+    arr.copy_from(&bytes[..]);
+    arr
+}
+
+#[wasm_bindgen]
+pub fn load_state(bytes: Uint8Array) -> bool {
     ENGINE.with(|e| {
         if let Some(ref mut eng) = *e.borrow_mut() {
-            eng.draw_paths.remove(&id).is_some()
+            let bytes = bytes.to_vec();
+            eng.log(Input::LoadState, || byte_args(&bytes));
+            eng.load_state(&bytes)
         } else { false }
     })
 }
 
 #[wasm_bindgen]
-pub fn clear_draw_paths() {
-    ENGINE.with(|e| {
-        if let Some(ref mut eng) = *e.borrow_mut() {
-            eng.draw_paths.clear();
-        }
-    });
+pub fn export_composition() -> String {
+    ENGINE.with(|e| match *e.borrow() {
+        Some(ref eng) => eng.composition_json(),
+        None => String::new(),
+    })
 }
 
 #[wasm_bindgen]
@@ -666,7 +4177,7 @@ pub fn get_draw_paths_count() -> u32 {
     })
 }
 
-// Pure helpers for testing image resizing logic without wasm-bindgen types.
+// Pure image resizing helpers (shared by the exports; testable without wasm-bindgen types).
 fn resize_nearest_rgba(src: &[u8], sw: u32, sh: u32, ow: u32, oh: u32) -> Vec<u8> {
     let sw = sw.max(1);
     let sh = sh.max(1);
@@ -721,9 +4232,7 @@ fn resize_bilinear_rgba(src: &[u8], sw: u32, sh: u32, ow: u32, oh: u32) -> Vec<u
                 let c11 = src[i11 + ch] as f32;
                 let c0 = c00 + (c10 - c00) * wx;
                 let c1 = c01 + (c11 - c01) * wx;
-                let mut v = (c0 + (c1 - c0) * wy).round();
-                if v < 0.0 { v = 0.0; }
-                if v > 255.0 { v = 255.0; }
+                let v = (c0 + (c1 - c0) * wy).round().clamp(0.0, 255.0);
                 dst[di + ch] = v as u8;
             }
         }
@@ -789,6 +4298,1198 @@ mod tests {
         assert!(e.particles.is_empty(), "expired particles should be removed");
     }
 
+    #[test]
+    fn pointer_down_captures_topmost_node() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 100.0, 100.0, 0.0, 0.0, 0.0, 2.0, 50.0, 50.0, 100.0, 100.0, 0.0, 0.0, 0.0], 8);
+        // Pointer id 7 is unrelated to any node id
+        e.apply_pointers(&[7.0, 60.0, 60.0, 0.5, 1.0]);
+        assert_eq!(e.pointers[&7].node, Some(2), "overlap should resolve to the last inserted node");
+        assert_eq!(e.events.ints, vec![1, 2, 0, 0]);
+        e.apply_pointers(&[7.0, 70.0, 80.0, 0.5, 1.0]);
+        assert_eq!((e.nodes[1].x, e.nodes[1].y), (60.0, 70.0));
+        assert_eq!((e.nodes[0].x, e.nodes[0].y), (0.0, 0.0));
+        e.apply_pointers(&[7.0, 70.0, 80.0, 0.0, 0.0]);
+        assert!(e.pointers.is_empty());
+        assert!(!e.nodes[1].grabbing);
+    }
+
+    #[test]
+    fn pointer_on_empty_space_captures_nothing() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 10.0, 10.0, 0.0, 0.0, 0.0], 8);
+        e.apply_pointers(&[1.0, 50.0, 50.0, 0.5, 1.0]);
+        assert_eq!(e.pointers[&1].node, None);
+        e.apply_pointers(&[1.0, 5.0, 5.0, 0.5, 1.0]);
+        assert_eq!((e.nodes[0].x, e.nodes[0].y), (0.0, 0.0));
+        assert!(e.events.ints.is_empty());
+    }
+
+    #[test]
+    fn second_pointer_takes_over_drag_on_release() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 100.0, 100.0, 0.0, 0.0, 0.0], 8);
+        e.apply_pointers(&[1.0, 10.0, 10.0, 0.5, 1.0, 2.0, 50.0, 50.0, 0.5, 1.0]);
+        e.apply_pointers(&[1.0, 10.0, 10.0, 0.0, 0.0]);
+        assert!(e.nodes[0].grabbing, "remaining pointer keeps the node");
+        e.apply_pointers(&[2.0, 60.0, 55.0, 0.5, 1.0]);
+        assert_eq!((e.nodes[0].x, e.nodes[0].y), (10.0, 5.0));
+        e.apply_pointers(&[2.0, 60.0, 55.0, 0.0, 0.0]);
+        assert_eq!(e.events.ints, vec![1, 1, 0, 0, 20, 1, 1, 2, 21, 1, 0, 0, 2, 1, 0, 0]);
+    }
+
+    #[test]
+    fn pinch_scales_and_rotates_about_centroid() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 100.0, 100.0, 0.0, 0.0, 0.0], 8);
+        e.apply_pointers(&[1.0, 40.0, 50.0, 0.5, 1.0, 2.0, 60.0, 50.0, 0.5, 1.0]);
+        assert!(e.nodes[0].pinch.is_some());
+        // Spread to twice the span and turn a quarter, centroid unchanged
+        e.apply_pointers(&[1.0, 50.0, 30.0, 0.5, 1.0, 2.0, 50.0, 70.0, 0.5, 1.0]);
+        let n = &e.nodes[0];
+        assert!((n.scale_x - 2.0).abs() < 1e-4 && (n.scale_y - 2.0).abs() < 1e-4);
+        assert!((n.angle - std::f32::consts::FRAC_PI_2).abs() < 1e-4);
+        assert!((n.x - 0.0).abs() < 1e-3 && (n.y - 0.0).abs() < 1e-3, "center stays at the centroid");
+        // Moving both pointers translates by the centroid delta
+        e.apply_pointers(&[1.0, 60.0, 40.0, 0.5, 1.0, 2.0, 60.0, 80.0, 0.5, 1.0]);
+        assert!((e.nodes[0].x - 10.0).abs() < 1e-3 && (e.nodes[0].y - 10.0).abs() < 1e-3);
+        e.apply_pointers(&[2.0, 60.0, 80.0, 0.0, 0.0]);
+        assert!(e.nodes[0].pinch.is_none());
+        assert_eq!(&e.events.ints[4..], &[20, 1, 1, 2, 21, 1, 0, 0]);
+        // Remaining pointer drags without a jump
+        e.apply_pointers(&[1.0, 65.0, 40.0, 0.5, 1.0]);
+        assert!((e.nodes[0].x - 15.0).abs() < 1e-3 && (e.nodes[0].y - 10.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn hit_test_honors_node_rotation() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[1.0, 0.0, 40.0, 100.0, 20.0, 0.0, 0.0, 0.0], 8);
+        assert_eq!(e.hit_test_world(50.0, 5.0), None);
+        e.nodes[0].angle = std::f32::consts::FRAC_PI_2;
+        e.grid.insert(1, e.nodes[0].aabb());
+        assert_eq!(e.hit_test_world(50.0, 5.0), Some(1));
+        assert_eq!(e.hit_test_world(5.0, 50.0), None);
+    }
+
+    #[test]
+    fn upsert_v2_carries_transform_into_output() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[3.0, 10.0, 20.0, 40.0, 40.0, 0.0, 0.0, 0.0, 0.5, 2.0, 3.0, 0.0, 0.0], 13);
+        assert_eq!(e.transforms(), vec![3.0, 10.0, 20.0, 0.5, 2.0, 3.0, 0.0]);
+        // A v1 upsert of the same node keeps its transform
+        e.upsert_nodes(&[3.0, 15.0, 20.0, 40.0, 40.0, 0.0, 0.0, 0.0], 8);
+        let n = &e.nodes[0];
+        assert_eq!((n.x, n.angle, n.scale_x, n.scale_y, n.pivot_x), (15.0, 0.5, 2.0, 3.0, 0.0));
+    }
+
+    #[test]
+    fn bounds_clamp_uses_rotated_box() {
+        let mut e = make_engine_with_bounds(0.0, 0.0, 200.0, 200.0);
+        // 100x20 bar turned upright about its center spans y in [-40, 60]
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 100.0, 20.0, 0.0, 0.0, 0.0, std::f32::consts::FRAC_PI_2, 1.0, 1.0, 0.5, 0.5], 13);
+        e.step(0.016);
+        let (l, t, _, _) = e.nodes[0].aabb();
+        assert!((t - 0.0).abs() < 1e-3, "top edge of the rotated box sits on the bound");
+        assert!((l - 40.0).abs() < 1e-3, "x is already inside and untouched");
+        assert!((e.nodes[0].y - 40.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn spatial_queries_follow_moved_nodes() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[
+            1.0, 0.0, 0.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+            2.0, 1000.0, 1000.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+            3.0, 20.0, 20.0, 600.0, 40.0, 0.0, 0.0, 0.0,
+        ], 8);
+        assert_eq!(e.hit_test_world(1020.0, 1020.0), Some(2));
+        assert_eq!(e.hit_test_world(30.0, 30.0), Some(3));
+        assert_eq!(e.query_rect_world(500.0, 0.0, -10.0, 100.0), vec![1, 3]);
+        // Drag node 2 across several cells; the index tracks it
+        e.apply_pointers(&[9.0, 1010.0, 1010.0, 0.5, 1.0]);
+        e.apply_pointers(&[9.0, 10.0, 310.0, 0.5, 1.0]);
+        assert_eq!(e.hit_test_world(1020.0, 1020.0), None);
+        assert_eq!(e.hit_test_world(5.0, 305.0), Some(2));
+        assert_eq!(e.query_rect_world(0.0, 250.0, 100.0, 400.0), vec![2]);
+    }
+
+    #[test]
+    fn spatial_grid_handles_oversized_nodes() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[1.0, -1.0e6, -1.0e6, 2.0e6, 2.0e6, 0.0, 0.0, 0.0, 2.0, 10.0, 10.0, 5.0, 5.0, 0.0, 0.0, 0.0], 8);
+        assert_eq!(e.grid.large, vec![1]);
+        assert_eq!(e.hit_test_world(12.0, 12.0), Some(2));
+        assert_eq!(e.hit_test_world(5000.0, 5000.0), Some(1));
+    }
+
+    #[test]
+    fn dragged_node_pushes_collide_nodes() {
+        let mut e = Engine::new(0);
+        let c = FLAG_COLLIDE as f32;
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 50.0, 50.0, 0.0, 0.0, c, 2.0, 100.0, 0.0, 50.0, 50.0, 0.0, 0.0, c], 8);
+        e.step(0.016);
+        assert!(e.events.ints.is_empty());
+        e.apply_pointers(&[1.0, 10.0, 10.0, 0.5, 1.0]);
+        e.apply_pointers(&[1.0, 80.0, 12.0, 0.5, 1.0]);
+        e.events.clear();
+        e.step(0.016);
+        assert_eq!((e.nodes[0].x, e.nodes[1].x), (70.0, 120.0), "grabbed node wins the push");
+        assert_eq!(e.events.ints, vec![30, 1, 2, 0, 3, 1, 1, 0]);
+        e.events.clear();
+        e.step(0.016);
+        assert!(e.events.ints.is_empty(), "resting contact does not re-fire");
+        e.apply_pointers(&[1.0, 10.0, 12.0, 0.5, 1.0]);
+        e.step(0.016);
+        assert_eq!(e.events.ints, vec![31, 1, 2, 0, 3, 1, 1, 0]);
+    }
+
+    #[test]
+    fn pinned_node_blocks_dragged_node() {
+        let mut e = Engine::new(0);
+        let c = FLAG_COLLIDE as f32;
+        let cp = (FLAG_COLLIDE | FLAG_PINNED) as f32;
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 50.0, 50.0, 0.0, 0.0, c, 2.0, 100.0, 0.0, 50.0, 50.0, 0.0, 0.0, cp], 8);
+        e.apply_pointers(&[1.0, 10.0, 10.0, 0.5, 1.0]);
+        e.apply_pointers(&[1.0, 80.0, 10.0, 0.5, 1.0]);
+        e.step(0.016);
+        assert_eq!((e.nodes[0].x, e.nodes[1].x), (50.0, 100.0));
+    }
+
+    #[test]
+    fn nodes_without_collide_flag_overlap_freely() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 50.0, 50.0, 0.0, 0.0, FLAG_COLLIDE as f32, 2.0, 10.0, 10.0, 50.0, 50.0, 0.0, 0.0, 0.0], 8);
+        e.step(0.016);
+        assert_eq!((e.nodes[0].x, e.nodes[1].x), (0.0, 10.0));
+        assert!(e.events.ints.is_empty());
+    }
+
+    #[test]
+    fn release_hands_pointer_velocity_to_node() {
+        let mut e = Engine::new(0);
+        e.inertia = 1.0;
+        e.damping = 0.5;
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 50.0, 50.0, 0.0, 0.0, 0.0], 8);
+        e.apply_pointers(&[1.0, 10.0, 10.0, 0.5, 1.0]);
+        // Slow start outside the window, then a fast flick
+        for i in 1..=10 {
+            e.step(0.02);
+            let x = if i <= 5 { 10.0 + i as f32 } else { 15.0 + (i - 5) as f32 * 20.0 };
+            e.apply_pointers(&[1.0, x, 10.0, 0.5, 1.0]);
+        }
+        e.apply_pointers(&[1.0, 115.0, 10.0, 0.0, 0.0]);
+        let n = &e.nodes[0];
+        assert!((n.vx - 1000.0).abs() < 1.0, "vx = {}", n.vx);
+        assert_eq!(n.vy, 0.0);
+        let x0 = n.x;
+        e.step(0.02);
+        assert!(e.nodes[0].x > x0 + 15.0, "node keeps gliding");
+        assert!(e.nodes[0].vx < 1000.0, "and decays under damping");
+    }
+
+    #[test]
+    fn release_after_hold_does_not_throw() {
+        let mut e = Engine::new(0);
+        e.inertia = 1.0;
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 50.0, 50.0, 0.0, 0.0, 0.0], 8);
+        e.apply_pointers(&[1.0, 10.0, 10.0, 0.5, 1.0]);
+        e.step(0.02);
+        e.apply_pointers(&[1.0, 60.0, 10.0, 0.5, 1.0]);
+        for _ in 0..10 { e.step(0.02); }
+        e.apply_pointers(&[1.0, 60.0, 10.0, 0.0, 0.0]);
+        assert_eq!((e.nodes[0].vx, e.nodes[0].vy), (0.0, 0.0));
+    }
+
+    #[test]
+    fn dragged_node_snaps_to_guides() {
+        let mut e = Engine::new(0);
+        e.guide_threshold = 5.0;
+        e.upsert_nodes(&[
+            1.0, 0.0, 0.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+            2.0, 200.0, 100.0, 40.0, 40.0, 0.0, 0.0, 0.0,
+            3.0, 400.0, 400.0, 100.0, 100.0, 0.0, 0.0, 0.0,
+        ], 8);
+        e.apply_pointers(&[1.0, 10.0, 10.0, 0.5, 1.0]);
+        // Left edge lands 3px right of node 2's left edge, top 2px below node 2's top
+        e.apply_pointers(&[1.0, 213.0, 112.0, 0.5, 1.0]);
+        e.step(0.016);
+        assert_eq!((e.nodes[0].x, e.nodes[0].y), (200.0, 100.0));
+        assert_eq!(e.guides, vec![0.0, 200.0, 1.0, 2.0, 1.0, 100.0, 1.0, 2.0]);
+        // Releasing clears the guides
+        e.apply_pointers(&[1.0, 213.0, 112.0, 0.0, 0.0]);
+        e.step(0.016);
+        assert!(e.guides.is_empty());
+    }
+
+    #[test]
+    fn guides_align_centers_and_respect_threshold() {
+        let mut e = Engine::new(0);
+        e.guide_threshold = 5.0;
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 20.0, 20.0, 0.0, 0.0, 0.0, 2.0, 100.0, 0.0, 60.0, 60.0, 0.0, 0.0, 0.0], 8);
+        e.apply_pointers(&[1.0, 10.0, 10.0, 0.5, 1.0]);
+        // Center y 28 -> node 2 center 30; x far from everything
+        e.apply_pointers(&[1.0, 310.0, 28.0, 0.5, 1.0]);
+        e.step(0.016);
+        assert_eq!((e.nodes[0].x, e.nodes[0].y), (300.0, 20.0));
+        assert_eq!(e.guides, vec![1.0, 30.0, 1.0, 2.0]);
+        e.guide_centers = false;
+        e.step(0.016);
+        e.apply_pointers(&[1.0, 310.0, 28.0, 0.5, 1.0]);
+        e.step(0.016);
+        assert_eq!(e.nodes[0].y, 18.0);
+        assert!(e.guides.is_empty());
+        // Once the viewport is known only nodes in view are guide sources
+        e.guide_centers = true;
+        e.autoscroll_margin = 0.0;
+        e.view_w = 200.0; e.view_h = 200.0; e.pan_x = -250.0;
+        e.apply_pointers(&[1.0, 60.0, 25.0, 0.5, 1.0]);
+        e.step(0.016);
+        assert_eq!(e.nodes[0].y, 15.0);
+        assert!(e.guides.is_empty());
+        e.pan_x = -50.0;
+        e.apply_pointers(&[1.0, 260.0, 28.0, 0.5, 1.0]);
+        e.step(0.016);
+        assert_eq!(e.nodes[0].y, 20.0);
+    }
+
+    #[test]
+    fn dragging_a_selected_node_moves_the_selection() {
+        let mut e = make_engine_with_bounds(0.0, 0.0, 500.0, 500.0);
+        e.upsert_nodes(&[
+            1.0, 100.0, 100.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+            2.0, 300.0, 120.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+            3.0, 100.0, 300.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+        ], 8);
+        e.select(&[1, 2, 99]);
+        assert_eq!(e.selection.iter().copied().collect::<Vec<_>>(), vec![1, 2]);
+        e.apply_pointers(&[1.0, 110.0, 110.0, 0.5, 1.0]);
+        e.apply_pointers(&[1.0, 130.0, 150.0, 0.5, 1.0]);
+        e.step(0.016);
+        assert_eq!((e.nodes[0].x, e.nodes[0].y), (120.0, 140.0));
+        assert_eq!((e.nodes[1].x, e.nodes[1].y), (320.0, 160.0));
+        assert_eq!((e.nodes[2].x, e.nodes[2].y), (100.0, 300.0), "unselected node stays put");
+        // Union box hits the right bound; both stop together
+        e.apply_pointers(&[1.0, 300.0, 150.0, 0.5, 1.0]);
+        e.step(0.016);
+        assert_eq!((e.nodes[0].x, e.nodes[1].x), (250.0, 450.0));
+        e.apply_pointers(&[1.0, 300.0, 150.0, 0.0, 0.0]);
+        assert!(e.nodes.iter().all(|n| !n.grabbing && n.group_leader.is_none()));
+        // Only the leader reports moves; the fast 190px move ends in a rightward swipe
+        let kinds: Vec<i32> = e.events.ints.chunks(4).map(|ev| ev[0]).collect();
+        assert_eq!(kinds, vec![1, 3, 3, 2, 14]);
+        assert!(e.events.ints.chunks(4).all(|ev| ev[1] == 1));
+    }
+
+    #[test]
+    fn selection_toggle_and_deselect() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0], 8);
+        e.toggle_selection(&[1, 2]);
+        e.toggle_selection(&[1]);
+        assert_eq!(e.selection.iter().copied().collect::<Vec<_>>(), vec![2]);
+        e.deselect(&[2]);
+        assert!(e.selection.is_empty());
+        // Grabbing an unselected node (2 is on top) leaves the selection alone
+        e.select(&[1]);
+        e.apply_pointers(&[1.0, 0.5, 0.5, 0.5, 1.0]);
+        assert!(!e.nodes[0].grabbing && e.nodes[1].grabbing);
+    }
+
+    #[test]
+    fn children_follow_parent_transform() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 100.0, 100.0, 0.0, 0.0, 0.0, 2.0, 60.0, 40.0, 20.0, 20.0, 0.0, 0.0, 0.0], 8);
+        assert_eq!(e.group(1, &[2, 1, 7]), 1);
+        assert_eq!(e.locals()[7..], [2.0, 1.0, 60.0, 40.0, 0.0, 1.0, 1.0]);
+        // Drag the parent from an empty spot; the child keeps its offset
+        e.apply_pointers(&[1.0, 10.0, 10.0, 0.5, 1.0]);
+        e.apply_pointers(&[1.0, 40.0, 20.0, 0.5, 1.0]);
+        assert_eq!((e.nodes[1].x, e.nodes[1].y), (90.0, 50.0));
+        e.apply_pointers(&[1.0, 40.0, 20.0, 0.0, 0.0]);
+        // Turn and grow the parent about its center
+        e.nodes[0].angle = std::f32::consts::FRAC_PI_2;
+        e.nodes[0].scale_x = 2.0;
+        e.nodes[0].scale_y = 2.0;
+        e.step(0.016);
+        let c = &e.nodes[1];
+        let (cx, cy) = c.pivot_world();
+        // Parent (now 200x200) is clamped so its center sits at (100, 100)
+        assert!((cx - 100.0).abs() < 1e-3 && (cy - 140.0).abs() < 1e-3, "child pivot at ({cx}, {cy})");
+        assert!((c.angle - std::f32::consts::FRAC_PI_2).abs() < 1e-5 && (c.scale_x - 2.0).abs() < 1e-5);
+        let l = &e.locals()[7..];
+        assert!((l[2] - 60.0).abs() < 1e-3 && (l[3] - 40.0).abs() < 1e-3 && l[4].abs() < 1e-5);
+    }
+
+    #[test]
+    fn group_and_ungroup_preserve_world_position() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[
+            1.0, 0.0, 0.0, 100.0, 100.0, 0.0, 0.0, 0.0, 0.6, 1.5, 1.5, 0.5, 0.5,
+            2.0, 30.0, 70.0, 20.0, 10.0, 0.0, 0.0, 0.0, 0.2, 1.0, 1.0, 0.5, 0.5,
+        ], 13);
+        let before = e.transforms();
+        assert!(e.set_parent(2, Some(1)));
+        assert!(!e.set_parent(1, Some(2)), "cycles are rejected");
+        let after = e.transforms();
+        for (a, b) in before.iter().zip(&after) { assert!((a - b).abs() < 1e-3); }
+        assert_eq!(e.ungroup(1), 1);
+        assert_eq!(e.nodes[1].parent, None);
+        for (a, b) in before.iter().zip(&e.transforms()) { assert!((a - b).abs() < 1e-3); }
+    }
+
+    #[test]
+    fn z_order_drives_hit_testing_and_output() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[
+            1.0, 0.0, 0.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+            2.0, 10.0, 10.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+            3.0, 20.0, 20.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+        ], 8);
+        let ids = |e: &Engine| e.stacking().into_iter().map(|i| e.nodes[i].id).collect::<Vec<_>>();
+        assert_eq!(e.hit_test_world(30.0, 30.0), Some(3));
+        assert!(e.bring_to_front(1));
+        assert!(e.bring_to_front(2));
+        assert!(e.bring_to_front(3));
+        assert!(e.bring_to_front(1));
+        assert_eq!(ids(&e), vec![2, 3, 1]);
+        assert_eq!(e.hit_test_world(30.0, 30.0), Some(1));
+        assert_eq!(e.query_rect_world(0.0, 0.0, 100.0, 100.0), vec![2, 3, 1]);
+        assert_eq!(e.transforms().iter().step_by(7).copied().collect::<Vec<_>>(), vec![2.0, 3.0, 1.0]);
+        assert!(e.send_to_back(3));
+        assert_eq!(ids(&e), vec![3, 2, 1]);
+        e.upsert_nodes(&[5.0, 0.0, 0.0, 5.0, 5.0, 0.0, 0.0, 0.0], 8);
+        assert_eq!(ids(&e), vec![3, 2, 1, 5], "new nodes land on top");
+        assert!(e.shift_z(3, 1));
+        assert_eq!(ids(&e), vec![2, 3, 1, 5]);
+        assert!(e.shift_z(2, -1), "lowering the bottom node is a no-op");
+        assert_eq!(ids(&e), vec![2, 3, 1, 5]);
+        assert!(!e.shift_z(42, 1));
+        // Re-upserting keeps the stacking
+        e.upsert_nodes(&[2.0, 10.0, 10.0, 50.0, 50.0, 0.0, 0.0, 0.0, 4.0, 0.0, 0.0, 5.0, 5.0, 0.0, 0.0, 0.0], 8);
+        assert_eq!(ids(&e), vec![2, 3, 1, 5, 4]);
+        // Raising and lowering trade z values instead of renumbering
+        let z = |e: &Engine, id: i32| e.nodes[e.index[&id]].z;
+        e.set_z(e.index[&4], 100);
+        let z5 = z(&e, 5);
+        assert!(e.shift_z(4, -1));
+        assert_eq!((z(&e, 4), z(&e, 5)), (z5, 100));
+        // Extreme explicit z leaves no room above or below: re-rank instead of overflowing
+        e.set_z(e.index[&1], i32::MAX);
+        e.set_z(e.index[&2], i32::MIN);
+        assert!(e.bring_to_front(3));
+        assert!(e.send_to_back(5));
+        assert_eq!(ids(&e), vec![5, 2, 4, 1, 3]);
+        e.upsert_nodes(&[6.0, 0.0, 0.0, 5.0, 5.0, 0.0, 0.0, 0.0], 8);
+        assert_eq!(ids(&e).last(), Some(&6));
+    }
+
+    #[test]
+    fn handle_drag_resizes_within_size_limits() {
+        let mut e = make_engine_with_bounds(0.0, 0.0, 500.0, 500.0);
+        e.upsert_nodes(&[
+            1.0, 100.0, 100.0, 100.0, 80.0, 0.0, 0.0, 8.0,
+            2.0, 190.0, 60.0, 40.0, 40.0, 0.0, 0.0, 0.0,
+        ], 8);
+        // South-east corner: top-left stays put
+        e.apply_pointers(&[1.0, 200.0, 180.0, 0.5, 1.0]);
+        e.apply_pointers(&[1.0, 250.0, 230.0, 0.5, 1.0]);
+        let n = &e.nodes[0];
+        assert_eq!((n.x, n.y, n.w, n.h), (100.0, 100.0, 150.0, 130.0));
+        e.apply_pointers(&[1.0, 0.0, 0.0, 0.5, 1.0]);
+        e.step(0.016);
+        let n = &e.nodes[0];
+        assert_eq!((n.x, n.y, n.w, n.h), (100.0, 100.0, 20.0, 20.0), "clamped to the minimum size");
+        e.apply_pointers(&[1.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(e.events.ints, vec![40, 1, 4, 0, 41, 1, 4, 0]);
+        assert_eq!(e.sizes()[..3], [1.0, 20.0, 20.0]);
+        // West edge: right edge stays put
+        e.events.clear();
+        e.apply_pointers(&[1.0, 100.0, 110.0, 0.5, 1.0]);
+        e.apply_pointers(&[1.0, 60.0, 150.0, 0.5, 1.0]);
+        let n = &e.nodes[0];
+        assert_eq!((n.x, n.y, n.w, n.h), (60.0, 100.0, 60.0, 20.0));
+        e.apply_pointers(&[1.0, 60.0, 150.0, 0.0, 0.0]);
+        assert_eq!(e.events.ints, vec![40, 1, 7, 0, 41, 1, 7, 0]);
+        // A handle under another node's body is not reachable; nodes without the flag just drag
+        e.events.clear();
+        e.upsert_nodes(&[1.0, 100.0, 100.0, 100.0, 80.0, 0.0, 0.0, 8.0], 8);
+        e.apply_pointers(&[1.0, 200.0, 100.0, 0.5, 1.0]);
+        e.apply_pointers(&[1.0, 200.0, 100.0, 0.0, 0.0]);
+        assert_eq!(e.events.ints, vec![1, 2, 0, 0, 2, 2, 0, 0]);
+        // Handle size is in screen px: at pixel ratio 2 the 12 px handle reaches 3 world px
+        e.events.clear();
+        e.pixel_ratio = 2.0;
+        e.apply_pointers(&[1.0, 408.0, 300.0, 0.5, 1.0]);
+        e.apply_pointers(&[1.0, 408.0, 300.0, 0.0, 0.0]);
+        assert!(e.events.ints.is_empty());
+        e.apply_pointers(&[1.0, 404.0, 300.0, 0.5, 1.0]);
+        assert_eq!(e.events.ints[0], 40);
+    }
+
+    #[test]
+    fn locked_resize_respects_grid_and_bounds() {
+        let mut e = make_engine_with_bounds(0.0, 0.0, 300.0, 300.0);
+        e.upsert_nodes(&[2.0, 100.0, 100.0, 100.0, 50.0, 0.0, 0.0, 24.0], 8);
+        // East edge drives width; height follows and grows downward
+        e.apply_pointers(&[1.0, 200.0, 125.0, 0.5, 1.0]);
+        e.apply_pointers(&[1.0, 400.0, 125.0, 0.5, 1.0]);
+        let n = &e.nodes[0];
+        assert_eq!((n.x, n.y, n.w, n.h), (100.0, 100.0, 200.0, 100.0), "stops at the right bound");
+        e.grid_x = 25.0;
+        e.grid_y = 25.0;
+        e.apply_pointers(&[1.0, 237.0, 125.0, 0.5, 1.0]);
+        let n = &e.nodes[0];
+        assert_eq!((n.w, n.h), (125.0, 62.5), "moving edge snaps to x = 225");
+        e.apply_pointers(&[1.0, 237.0, 125.0, 0.0, 0.0]);
+        assert_eq!(e.events.ints, vec![40, 2, 3, 0, 41, 2, 3, 0]);
+    }
+
+    fn gesture_events(e: &Engine) -> Vec<[i32; 4]> {
+        e.events.ints.chunks(4).filter(|ev| (10..20).contains(&ev[0])).map(|ev| [ev[0], ev[1], ev[2], ev[3]]).collect()
+    }
+
+    #[test]
+    fn long_press_fires_once_for_a_still_pointer() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 100.0, 100.0, 0.0, 0.0, 0.0, 2.0, 200.0, 0.0, 100.0, 100.0, 0.0, 0.0, 0.0], 8);
+        e.apply_pointers(&[7.0, 50.0, 50.0, 0.5, 1.0, 8.0, 250.0, 50.0, 0.5, 1.0]);
+        e.step(0.3);
+        // Pointer 8 wanders past the move threshold before the hold completes
+        e.apply_pointers(&[7.0, 52.0, 50.0, 0.5, 1.0, 8.0, 270.0, 50.0, 0.5, 1.0]);
+        assert!(gesture_events(&e).is_empty());
+        e.step(0.3);
+        e.step(0.3);
+        assert_eq!(gesture_events(&e), vec![[12, 1, 7, 0]]);
+        // Releasing after a long press is not a tap
+        e.apply_pointers(&[7.0, 52.0, 50.0, 0.0, 0.0, 8.0, 270.0, 50.0, 0.0, 0.0]);
+        e.step(0.5);
+        assert_eq!(gesture_events(&e), vec![[12, 1, 7, 0]]);
+    }
+
+    #[test]
+    fn tap_sequences_complete_at_max_taps() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 100.0, 100.0, 0.0, 0.0, 0.0], 8);
+        let tap = |e: &mut Engine| {
+            e.apply_pointers(&[1.0, 50.0, 50.0, 0.5, 1.0]);
+            e.apply_pointers(&[1.0, 50.0, 50.0, 0.0, 0.0]);
+            e.step(0.1);
+        };
+        // Default: the second tap completes a double tap right away
+        tap(&mut e);
+        tap(&mut e);
+        assert_eq!(gesture_events(&e), vec![[11, 1, 2, 0]]);
+        e.step(0.5);
+        e.events.clear();
+        e.max_taps = 3;
+        tap(&mut e);
+        tap(&mut e);
+        tap(&mut e);
+        assert_eq!(gesture_events(&e), vec![[13, 1, 3, 0]]);
+        // A pair that stops short reports as a double tap once the delay passes
+        e.events.clear();
+        e.step(0.5);
+        tap(&mut e);
+        tap(&mut e);
+        e.step(0.3);
+        assert_eq!(gesture_events(&e), vec![[11, 1, 2, 0]]);
+    }
+
+    #[test]
+    fn fast_releases_classify_as_swipes() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[1.0, 200.0, 0.0, 100.0, 100.0, 0.0, 0.0, 0.0], 8);
+        let flick = |e: &mut Engine, from: (f32, f32), step: (f32, f32), dt: f32| {
+            e.apply_pointers(&[1.0, from.0, from.1, 0.5, 1.0]);
+            for i in 1..=5 {
+                e.step(dt);
+                e.apply_pointers(&[1.0, from.0 + step.0 * i as f32, from.1 + step.1 * i as f32, 0.5, 1.0]);
+            }
+            let end = (from.0 + step.0 * 5.0, from.1 + step.1 * 5.0);
+            e.apply_pointers(&[1.0, end.0, end.1, 0.0, 0.0]);
+        };
+        // Node flicked left at 1000 px/s
+        flick(&mut e, (250.0, 50.0), (-20.0, 2.0), 0.02);
+        let n = e.events.ints.len();
+        assert_eq!(e.events.ints[n - 4..], [14, 1, 2, 1005]);
+        // Empty space flicked up
+        e.events.clear();
+        flick(&mut e, (50.0, 300.0), (0.0, -20.0), 0.02);
+        assert_eq!(e.events.ints, vec![14, -1, 3, 1000]);
+        // Far but slow, then fast but short: neither is a swipe
+        e.events.clear();
+        flick(&mut e, (50.0, 300.0), (0.0, 20.0), 0.1);
+        flick(&mut e, (50.0, 300.0), (4.0, 0.0), 0.01);
+        assert!(e.events.ints.is_empty());
+    }
+
+    #[test]
+    fn drag_events_carry_float_payloads() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 100.0, 100.0, 0.0, 0.0, 0.0], 8);
+        e.apply_pointers(&[4.0, 10.0, 10.0, 0.5, 1.0]);
+        e.apply_pointers(&[4.0, 30.0, 25.0, 0.5, 1.0]);
+        e.step(0.05);
+        e.step(0.05);
+        e.apply_pointers(&[4.0, 40.0, 25.0, 0.5, 1.0]);
+        e.apply_pointers(&[4.0, 40.0, 25.0, 0.0, 0.0]);
+        assert_eq!(e.events.ints, vec![1, 1, 0, 0, 3, 1, 4, 0, 2, 1, 0, 0]);
+        assert_eq!(e.events.floats, vec![
+            0.0, 0.0, 10.0, 10.0,
+            20.0, 15.0, 20.0, 15.0,
+            30.0, 15.0, 100.0, 0.0,
+        ]);
+        // Tap payload is the release point, even when reported after the delay
+        e.events.clear();
+        e.step(0.5);
+        e.apply_pointers(&[4.0, 60.0, 70.0, 0.5, 1.0]);
+        e.apply_pointers(&[4.0, 60.0, 70.0, 0.0, 0.0]);
+        e.step(0.3);
+        assert_eq!(e.events.ints[8..], [10, 1, 1, 0]);
+        assert_eq!(e.events.floats[8..], [60.0, 70.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn event_frames_start_with_a_schema_header() {
+        let mut e = Engine::new(0);
+        assert_eq!(e.events.take_frame(), (vec![0, EVENT_SCHEMA_VERSION, 4, 0], vec![0.0; 4]));
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 100.0, 100.0, 0.0, 0.0, 0.0], 8);
+        e.apply_pointers(&[2.0, 10.0, 20.0, 0.5, 1.0]);
+        let (ints, floats) = e.events.take_frame();
+        assert_eq!(ints, vec![0, EVENT_SCHEMA_VERSION, 4, 1, EventKind::DragStart as i32, 1, 0, 0]);
+        assert_eq!(floats[4..], [0.0, 0.0, 10.0, 20.0]);
+        assert!(e.events.ints.is_empty() && e.events.floats.is_empty(), "drained");
+        // Codes and names are unique; every code is listed in the schema
+        let codes: HashSet<i32> = EventKind::ALL.iter().map(|&k| k as i32).collect();
+        let names: HashSet<&str> = EventKind::ALL.iter().map(|k| k.describe().0).collect();
+        assert_eq!((codes.len(), names.len()), (EventKind::ALL.len(), EventKind::ALL.len()));
+        assert!(!codes.contains(&0), "0 is reserved for the header");
+    }
+
+    #[test]
+    fn drop_zones_track_hover_and_drop() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[
+            1.0, 0.0, 0.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+            2.0, 0.0, 400.0, 50.0, 50.0, 0.0, 0.0, 256.0,
+        ], 8);
+        e.upsert_drop_zones(&[
+            100.0, 200.0, 0.0, 100.0, 100.0, 0.0, 0.0,
+            200.0, 0.0, 200.0, 100.0, 100.0, 256.0, 1.0,
+        ]);
+        let drop_events = |e: &Engine| -> Vec<[i32; 4]> {
+            e.events.ints.chunks(4).filter(|ev| (50..60).contains(&ev[0])).map(|ev| [ev[0], ev[1], ev[2], ev[3]]).collect()
+        };
+        // Pointer zone: enter, leave, re-enter, drop
+        e.apply_pointers(&[1.0, 10.0, 10.0, 0.5, 1.0]);
+        for x in [250.0, 400.0, 250.0] {
+            e.apply_pointers(&[1.0, x, 50.0, 0.5, 1.0]);
+            e.step(0.016);
+        }
+        e.apply_pointers(&[1.0, 250.0, 50.0, 0.0, 0.0]);
+        assert_eq!(drop_events(&e), vec![[50, 1, 100, 0], [51, 1, 100, 0], [50, 1, 100, 0], [52, 1, 100, 0]]);
+        let n = e.events.ints.len();
+        assert_eq!(e.events.ints[n - 8..n - 4], [52, 1, 100, 0], "drop comes before drag_end");
+        assert_eq!(e.events.floats[n - 8..n - 4], [240.0, 40.0, 250.0, 50.0]);
+        // Overlap zone only accepts flagged nodes
+        e.events.clear();
+        e.apply_pointers(&[1.0, 250.0, 50.0, 0.5, 1.0]);
+        e.apply_pointers(&[1.0, 60.0, 260.0, 0.5, 1.0]);
+        e.step(0.016);
+        e.apply_pointers(&[1.0, 60.0, 260.0, 0.0, 0.0]);
+        assert!(drop_events(&e).is_empty());
+        e.apply_pointers(&[2.0, 10.0, 410.0, 0.5, 1.0]);
+        e.apply_pointers(&[2.0, 10.0, 290.0, 0.5, 1.0]);
+        e.step(0.016);
+        assert_eq!(drop_events(&e), vec![[50, 2, 200, 0]]);
+        // Removing the zone ends the hover
+        e.events.clear();
+        assert!(e.remove_drop_zone(200));
+        assert_eq!(drop_events(&e), vec![[51, 2, 200, 0]]);
+        e.apply_pointers(&[2.0, 10.0, 290.0, 0.0, 0.0]);
+        assert_eq!(drop_events(&e).len(), 1);
+    }
+
+    #[test]
+    fn dragging_near_an_edge_pans_and_keeps_the_node_under_the_finger() {
+        let mut e = Engine::new(0);
+        e.left = f32::NEG_INFINITY; e.top = f32::NEG_INFINITY;
+        e.upsert_nodes(&[1.0, 100.0, 100.0, 50.0, 50.0, 0.0, 0.0, 0.0], 8);
+        e.apply_pointers(&[1.0, 120.0, 120.0, 0.5, 1.0]);
+        e.apply_pointers(&[1.0, 10.0, 120.0, 0.5, 1.0]);
+        e.step(0.1);
+        assert_eq!((e.pan_x, e.pan_y), (0.0, 0.0), "no viewport, no autoscroll");
+        e.view_w = 400.0; e.view_h = 300.0;
+        e.step(0.1);
+        // 38 of 48 px into the left margin: 38/48 * 1200 px/s for 0.1 s
+        assert!((e.pan_x - 95.0).abs() < 1e-3 && e.pan_y == 0.0);
+        assert!((e.nodes[0].x - (10.0 - 95.0 - 20.0)).abs() < 1e-3, "node stays under the finger");
+        let p = &e.pointers[&1];
+        let (sx, sy) = e.world_to_screen(p.x, p.y);
+        assert!((sx - 10.0).abs() < 1e-3 && (sy - 120.0).abs() < 1e-3);
+        // Past the bottom-right corner pans up/left at full speed; the middle does not pan
+        e.apply_pointers(&[1.0, 450.0, 350.0, 0.5, 1.0]);
+        let (px, py) = (e.pan_x, e.pan_y);
+        e.step(0.1);
+        assert!((e.pan_x - (px - 120.0)).abs() < 1e-3 && (e.pan_y - (py - 120.0)).abs() < 1e-3);
+        e.apply_pointers(&[1.0, 200.0, 150.0, 0.5, 1.0]);
+        let pan = (e.pan_x, e.pan_y);
+        e.step(0.1);
+        assert_eq!((e.pan_x, e.pan_y), pan);
+    }
+
+    #[test]
+    fn camera_zooms_about_a_point_within_limits_and_bounds() {
+        let mut e = Engine::new(0);
+        e.view_w = 400.0; e.view_h = 300.0;
+        e.pixel_ratio = 2.0;
+        // The world point under the cursor stays put
+        let before = e.screen_to_world(200.0, 100.0);
+        e.zoom_at(2.0, 200.0, 100.0);
+        assert_eq!(e.scale, 2.0);
+        assert_eq!(e.screen_to_world(200.0, 100.0), before);
+        e.zoom_at(100.0, 200.0, 100.0);
+        assert_eq!(e.scale, 10.0, "clamped to max zoom");
+        e.zoom_at(1e-4, 0.0, 0.0);
+        assert_eq!(e.scale, 0.1, "clamped to min zoom");
+        // Content bounds keep the viewport inside; narrower content is centered
+        e.scale = 1.0;
+        e.content = Some((0.0, 0.0, 1000.0, 200.0));
+        e.pan_by(100.0, -50.0);
+        assert_eq!((e.pan_x, e.pan_y), (0.0, 50.0));
+        e.pan_by(-5000.0, 0.0);
+        assert_eq!(e.pan_x, -600.0);
+    }
+
+    #[test]
+    fn camera_coasts_after_a_flick_and_animates_fits() {
+        let mut e = Engine::new(0);
+        e.view_w = 400.0; e.view_h = 300.0;
+        for _ in 0..5 {
+            e.pan_by(10.0, 0.0);
+            e.step(0.02);
+        }
+        e.release_pan();
+        assert!((e.cam_vx - 500.0).abs() < 1e-2 && e.cam_vy == 0.0);
+        let x = e.pan_x;
+        e.step(0.1);
+        assert!(e.pan_x > x + 49.0 && e.cam_vx < 500.0, "coasts and slows down");
+        for _ in 0..300 { e.step(0.02); }
+        assert_eq!(e.cam_vx, 0.0, "comes to rest");
+        // A press catches the flick
+        e.pan_by(10.0, 0.0); e.step(0.02); e.pan_by(10.0, 0.0); e.release_pan();
+        e.pointer_down(1, 0.0, 0.0);
+        assert_eq!(e.cam_vx, 0.0);
+        // fit_to_nodes eases to the padded fit of the nodes
+        e.upsert_nodes(&[
+            1.0, 100.0, 100.0, 100.0, 50.0, 0.0, 0.0, 0.0,
+            2.0, 300.0, 200.0, 100.0, 50.0, 0.0, 0.0, 0.0,
+        ], 8);
+        assert!(!e.fit_to_nodes(&[9], 20.0, 0.5));
+        assert!(e.fit_to_nodes(&[], 20.0, 0.5));
+        e.step(0.25);
+        assert!(e.cam_anim.is_some());
+        e.step(0.3);
+        assert!(e.cam_anim.is_none());
+        // Union 300 x 150 into 360 x 260 -> 1.2, centered on (250, 175)
+        assert!((e.scale - 1.2).abs() < 1e-5);
+        let (cx, cy) = e.camera_center();
+        assert!((cx - 250.0).abs() < 1e-3 && (cy - 175.0).abs() < 1e-3);
+        // A press stops a running animation, and a held node keeps up with the camera
+        e.apply_pointers(&[1.0, 0.0, 0.0, 0.0, 0.0]);
+        assert!(e.fit_to_nodes(&[1], 20.0, 0.5));
+        e.step(0.1);
+        let (sx, sy) = e.world_to_screen(150.0, 125.0);
+        e.apply_pointers(&[2.0, sx, sy, 0.5, 1.0]);
+        assert!(e.cam_anim.is_none());
+        e.pan_by(30.0, -20.0);
+        e.zoom_at(1.5, 10.0, 10.0);
+        let (nx, ny) = e.world_to_screen(e.nodes[0].x + 50.0, e.nodes[0].y + 25.0);
+        assert!((nx - sx).abs() < 1e-3 && (ny - sy).abs() < 1e-3);
+    }
+
+    #[test]
+    fn screen_and_world_conversions_round_trip() {
+        let mut e = Engine::new(0);
+        e.scale = 1.5; e.pan_x = -40.0; e.pan_y = 25.0; e.pixel_ratio = 2.0;
+        let world = e.map_points(&[100.0, 50.0, 400.0, 300.0, 7.0], true);
+        assert_eq!(world, vec![(50.0 + 40.0) / 1.5, 0.0, (200.0 + 40.0) / 1.5, (150.0 - 25.0) / 1.5]);
+        let screen = e.map_points(&world, false);
+        for (a, b) in screen.iter().zip([100.0, 50.0, 400.0, 300.0]) { assert!((a - b).abs() < 1e-3); }
+        // A press at a node's converted corner grabs it with no offset
+        e.upsert_nodes(&[1.0, 60.0, 60.0, 50.0, 50.0, 0.0, 0.0, 0.0], 8);
+        let (sx, sy) = e.world_to_screen(60.0, 60.0);
+        e.apply_pointers(&[1.0, sx, sy, 0.5, 1.0]);
+        let n = &e.nodes[0];
+        assert!(n.grabbing && n.grab_dx.abs() < 1e-3 && n.grab_dy.abs() < 1e-3);
+    }
+
+    #[test]
+    fn tweens_and_springs_animate_node_properties() {
+        let anim_ends = |e: &Engine| -> Vec<[i32; 4]> {
+            e.events.ints.chunks(4).filter(|ev| ev[0] == EventKind::AnimEnd as i32).map(|ev| [ev[0], ev[1], ev[2], ev[3]]).collect()
+        };
+        let mut e = Engine::new(0);
+        e.grid_x = 10.0;
+        e.upsert_nodes(&[
+            1.0, 0.0, 0.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+            2.0, 200.0, 0.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+        ], 8);
+        assert!(e.animate(1, AnimProp::X, 103.0, 1.0, 0, None));
+        assert!(e.animate(1, AnimProp::W, 100.0, 1.0, 1, None));
+        e.step(0.5);
+        assert_eq!(e.nodes[0].x, 51.5, "linear halfway, not grid-snapped mid-flight");
+        assert_eq!(e.nodes[0].w, 75.0, "ease-in-out is symmetric");
+        e.step(0.5);
+        assert_eq!((e.nodes[0].x, e.nodes[0].w), (100.0, 100.0), "lands on target, then snaps");
+        assert_eq!(anim_ends(&e), vec![[60, 1, 0, 0], [60, 1, 2, 0]]);
+        // A spring settles on its target
+        e.events.clear();
+        assert!(e.animate(2, AnimProp::Scale, 2.0, 0.0, 0, Some((170.0, 26.0))));
+        for _ in 0..120 { e.step(1.0 / 60.0); }
+        let n = &e.nodes[1];
+        assert_eq!((n.scale_x, n.scale_y), (2.0, 2.0));
+        assert_eq!(anim_ends(&e), vec![[60, 2, 5, 0]]);
+        // Restarting replaces, and a grab cancels where it stands
+        e.events.clear();
+        assert!(e.animate(1, AnimProp::Y, 300.0, 1.0, 0, None));
+        assert!(e.animate(1, AnimProp::Y, 100.0, 1.0, 0, None));
+        e.step(0.5);
+        e.pointer_down(1, 110.0, 60.0);
+        assert_eq!(anim_ends(&e), vec![[60, 1, 1, 1], [60, 1, 1, 1]]);
+        assert_eq!((e.events.floats[0], e.events.floats[4]), (0.0, 50.0), "values where they stopped");
+        assert!(e.tweens.is_empty());
+        assert!(!e.animate(1, AnimProp::X, 0.0, 1.0, 0, None), "held nodes refuse animations");
+        // Springs need stiffness; an undamped one ends after SPRING_MAX_S, and a stalled frame stays bounded
+        e.events.clear();
+        assert!(!e.animate(2, AnimProp::X, 0.0, 0.0, 0, Some((0.0, 10.0))));
+        assert!(e.animate(2, AnimProp::X, 100.0, 0.0, 0, Some((100.0, 0.0))));
+        for _ in 0..9 { e.step(1.0); }
+        assert_eq!(e.tweens.len(), 1, "still ringing: {}", e.nodes[1].x);
+        e.step(1.0);
+        assert!(e.tweens.is_empty());
+        assert_eq!(e.nodes[1].x, 100.0);
+        assert_eq!(anim_ends(&e), vec![[60, 2, 0, 0]]);
+        assert!(e.animate(2, AnimProp::X, 140.0, 0.0, 0, Some((100.0, 20.0))));
+        e.step(1e9);
+        assert_eq!(e.nodes[1].x, 140.0);
+    }
+
+    #[test]
+    fn history_undoes_gestures_and_grouped_edits() {
+        let mut e = Engine::new(0);
+        e.record(|e| e.upsert_nodes(&[
+            1.0, 0.0, 0.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+            2.0, 100.0, 0.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+        ], 8));
+        // A drag, taps included, is one step once the last pointer lifts
+        e.apply_pointers(&[1.0, 10.0, 10.0, 0.5, 1.0]);
+        e.apply_pointers(&[1.0, 30.0, 50.0, 0.5, 1.0]);
+        e.step(0.016);
+        e.apply_pointers(&[1.0, 40.0, 60.0, 0.5, 1.0]);
+        e.apply_pointers(&[1.0, 40.0, 60.0, 0.0, 0.0]);
+        e.apply_pointers(&[1.0, 110.0, 10.0, 0.5, 1.0]);
+        e.apply_pointers(&[1.0, 110.0, 10.0, 0.0, 0.0]);
+        assert_eq!(e.history.undo.len(), 2);
+        assert!(e.undo());
+        assert_eq!((e.nodes[0].x, e.nodes[0].y), (0.0, 0.0));
+        assert!(e.redo());
+        assert_eq!((e.nodes[0].x, e.nodes[0].y), (30.0, 50.0));
+        // Grouped edits: remove one node and resize the other in one step
+        e.begin_change();
+        e.remove_node(2);
+        e.upsert_nodes(&[1.0, 30.0, 50.0, 80.0, 80.0, 0.0, 0.0, 0.0], 8);
+        assert!(!e.undo(), "refused while a change is open");
+        let (nodes, paths) = e.history.base.as_ref().unwrap();
+        assert_eq!(nodes.keys().copied().collect::<Vec<_>>(), vec![1, 2], "only touched nodes are kept");
+        assert!(paths.is_empty());
+        e.end_change();
+        assert!(e.undo());
+        assert_eq!(e.nodes.len(), 2);
+        let n2 = &e.nodes[e.index[&2]];
+        assert_eq!((n2.x, n2.w), (100.0, 50.0));
+        assert_eq!(e.nodes[e.index[&1]].w, 50.0);
+        assert!(e.hit_test_world(110.0, 10.0) == Some(2), "restored nodes are hit-testable");
+        // A new edit drops the redo branch; undoing the first upsert removes the nodes
+        e.record(|e| e.bring_to_front(1));
+        assert!(!e.redo());
+        while e.undo() {}
+        assert!(e.nodes.is_empty() && e.index.is_empty());
+        // Paused edits (layout sync) apply without an undo step, even inside an open one
+        e.history.paused = true;
+        e.record(|e| e.upsert_nodes(&[3.0, 0.0, 0.0, 10.0, 10.0, 0.0, 0.0, 0.0], 8));
+        e.begin_change();
+        e.patch_nodes(PATCH_SIZE, &[3.0, 20.0, 20.0]);
+        e.history.paused = false;
+        e.end_change();
+        assert!(e.history.undo.is_empty() && e.nodes[0].w == 20.0);
+        e.remove_nodes(&[3]);
+        // A throw stays in its gesture's step until the node coasts to rest and snaps to the grid
+        e.record(|e| e.upsert_nodes(&[4.0, 0.0, 0.0, 10.0, 10.0, 0.0, 0.0, 0.0], 8));
+        (e.grid_x, e.grid_y, e.inertia, e.damping) = (10.0, 10.0, 1.0, 0.01);
+        e.apply_pointers(&[1.0, 5.0, 5.0, 0.5, 1.0]);
+        e.step(0.016);
+        e.apply_pointers(&[1.0, 25.0, 5.0, 0.5, 1.0]);
+        e.step(0.016);
+        e.apply_pointers(&[1.0, 25.0, 5.0, 0.0, 0.0]);
+        e.step(0.016);
+        assert!(e.nodes[0].vx > 0.0 && e.history.gesture, "still coasting");
+        assert!(e.history.undo.len() == 1 && e.can_undo());
+        for _ in 0..400 { e.step(0.016); }
+        assert!(!e.history.gesture && e.history.undo.len() == 2);
+        let x = e.nodes[0].x;
+        assert!(x > 25.0 && x % 10.0 == 0.0, "{x}");
+        assert!(e.undo());
+        assert_eq!(e.nodes[0].x, 0.0);
+        (e.grid_x, e.grid_y, e.inertia, e.damping) = (0.0, 0.0, 0.0, 1.0);
+        while e.undo() {}
+        // Depth bounds the stack
+        e.set_history_depth(2);
+        for i in 0..5 { e.record(|e| e.upsert_nodes(&[1.0, i as f32, 0.0, 10.0, 10.0, 0.0, 0.0, 0.0], 8)); }
+        assert_eq!(e.history.undo.len(), 2);
+        while e.undo() {}
+        assert_eq!(e.nodes[0].x, 2.0);
+    }
+
+    #[test]
+    fn removing_nodes_reindexes_and_forgets_them() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[
+            1.0, 0.0, 0.0, 50.0, 50.0, 0.0, 0.0, 2.0,
+            2.0, 40.0, 0.0, 50.0, 50.0, 0.0, 0.0, 2.0,
+            3.0, 200.0, 0.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+            4.0, 210.0, 10.0, 10.0, 10.0, 0.0, 0.0, 0.0,
+        ], 8);
+        e.set_parent(4, Some(3));
+        e.select(&[1, 3]);
+        e.step(0.016);
+        assert_eq!(e.contacts.len(), 1);
+        e.events.clear();
+        assert_eq!(e.remove_nodes(&[1, 3, 9]), 2);
+        assert_eq!(e.events.ints, vec![EventKind::CollisionEnd as i32, 1, 2, 0]);
+        assert_eq!(e.nodes.len(), 2);
+        for (i, n) in e.nodes.iter().enumerate() { assert_eq!(e.index[&n.id], i); }
+        assert!(e.selection.is_empty() && e.contacts.is_empty());
+        assert_eq!(e.hit_test_world(10.0, 10.0), None);
+        assert_eq!(e.hit_test_world(215.0, 15.0), Some(4), "children stay put as roots");
+        assert_eq!(e.nodes[e.index[&4]].parent, None);
+        e.clear_nodes();
+        assert!(e.nodes.is_empty() && e.index.is_empty());
+        assert!(e.query_rect_world(-1e4, -1e4, 1e4, 1e4).is_empty());
+    }
+
+    #[test]
+    fn patches_keep_the_drag_going() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 50.0, 50.0, 0.0, 0.0, 0.0], 8);
+        e.apply_pointers(&[1.0, 10.0, 10.0, 0.5, 1.0]);
+        assert_eq!(e.patch_nodes(PATCH_SIZE | PATCH_FLAGS, &[1.0, 80.0, 60.0, 2.0, 9.0, 1.0, 1.0, 0.0]), 1);
+        let n = &e.nodes[0];
+        assert!(n.grabbing && n.flags == 2 && (n.w, n.h) == (80.0, 60.0));
+        // Moving a held node keeps it at the new offset from the pointer
+        e.patch_nodes(PATCH_POSITION, &[1.0, 100.0, 0.0]);
+        e.apply_pointers(&[1.0, 20.0, 10.0, 0.5, 1.0]);
+        assert_eq!((e.nodes[0].x, e.nodes[0].y), (110.0, 0.0));
+        e.events.clear();
+        e.step(0.016);
+        assert_eq!(e.events.floats[2..4], [10.0, 0.0], "drag_move reports only the pointer's motion");
+        assert_eq!(e.patch_nodes(PATCH_POSITION, &[1.0, 0.0]), 0, "wrong stride");
+        assert_eq!(e.patch_nodes(0, &[1.0]), 0);
+    }
+
+    #[test]
+    fn state_snapshots_round_trip() {
+        let mut e = Engine::new(0);
+        e.upsert_nodes(&[
+            1.0, 10.0, 20.0, 100.0, 50.0, 0.0, 0.0, 0.0,
+            2.0, 30.0, 40.0, 20.0, 20.0, 0.0, 0.0, 0.0,
+        ], 8);
+        assert!(e.set_parent(2, Some(1)));
+        assert!(e.bring_to_front(1));
+        e.nodes[0].angle = 0.5;
+        e.draw_paths.insert(4, DrawPath { id: 4, points: vec![0.0, 0.0, 0.5, 0.0, 10.0, 5.0, 0.7, 16.0], color: 0xff0000ff, width: 3.0, closed: true });
+        e.particles.push(Particle { x: 1.0, y: 2.0, vx: 3.0, vy: 4.0, r: 1.5, life: 0.5 });
+        e.images.insert(9, Image { w: 1, h: 2, data: vec![1, 2, 3, 4, 5, 6, 7, 8] });
+        e.scale = 2.0; e.pan_x = -30.0; e.right = 800.0; e.grid_x = 10.0;
+        let bytes = e.save_state();
+        let transforms = e.transforms();
+
+        let mut f = Engine::new(0);
+        f.upsert_nodes(&[7.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0], 8);
+        assert!(f.load_state(&bytes));
+        assert_eq!(f.transforms(), transforms);
+        assert_eq!(f.nodes[1].parent, Some(1));
+        assert_eq!(f.draw_paths[&4], e.draw_paths[&4]);
+        assert_eq!((f.particles.len(), f.particles[0].vy), (1, 4.0));
+        assert_eq!(f.images[&9].data, e.images[&9].data);
+        assert_eq!((f.scale, f.pan_x, f.right, f.grid_x), (2.0, -30.0, 800.0, 10.0));
+        assert!(!f.index.contains_key(&7));
+        assert_eq!(f.save_state(), bytes);
+
+        // Bad magic, truncation and trailing bytes are rejected without touching the engine
+        let mut bad = bytes.clone();
+        bad[0] = b'X';
+        assert!(!f.load_state(&bad));
+        assert!(!f.load_state(&bytes[..bytes.len() - 1]));
+        bad = bytes.clone();
+        bad.push(0);
+        assert!(!f.load_state(&bad));
+        assert_eq!(f.nodes.len(), 2);
+    }
+
+    #[test]
+    fn composition_export_matches_canvas_shape() {
+        let mut e = Engine::new(0);
+        e.right = 640.0; e.bottom = 480.0;
+        e.upsert_nodes(&[1.0, 10.0, 20.0, 100.0, 50.0, 0.0, 0.0, 0.0], 8);
+        e.draw_paths.insert(2, DrawPath { id: 2, points: vec![1.0, 2.0, 0.5, 0.0, 3.0, 4.0, 0.25, 0.1], color: 0xff0033ff, width: 2.5, closed: false });
+        e.images.insert(1, Image { w: 4, h: 2, data: vec![0; 32] });
+        e.images.insert(5, Image { w: 3, h: 6, data: vec![0; 72] });
+        let json = e.composition_json();
+        assert!(json.starts_with(r#"{"version":"1","canvas":{"width":640,"height":480,"background":""}"#));
+        // Points are {x, y, pressure} objects and colors are CSS strings
+        assert!(json.contains(concat!(
+            r#""drawPaths":[{"id":2,"points":[{"x":1,"y":2,"pressure":0.5},{"x":3,"y":4,"pressure":0.25}],"#,
+            r#""color":"rgba(255, 51, 0, 1)","width":2.5,"closed":false}]"#,
+        )));
+        // Stored images sit on the node with their id, else at the origin at pixel size
+        assert!(json.contains(concat!(
+            r#""textLayers":[],"imageLayers":[{"id":1,"url":"","x":10,"y":20,"w":100,"h":50},"#,
+            r#"{"id":5,"url":"","x":0,"y":0,"w":3,"h":6}],"settings":{"particlesEnabled":false,"#,
+        )));
+        assert!(json.contains(r#""constraints":{"left":0,"top":0,"right":640,"bottom":480,"gridX":1,"gridY":1,"inertia":0,"damping":1}"#));
+        // Nodes ride in settings; no keys outside the schema
+        assert!(json.ends_with(r#""nodes":[{"id":1,"x":10,"y":20,"w":100,"h":50,"flags":0,"angle":0,"scaleX":1,"scaleY":1,"pivotX":0.5,"pivotY":0.5,"z":0,"parent":null}]}}"#));
+        assert!(!json.contains(r#"],"nodes""#));
+        // Unbounded canvases fall back to the viewport size and never emit non-finite numbers
+        e.right = f32::INFINITY;
+        e.view_w = 320.0; e.view_h = 200.0;
+        let json = e.composition_json();
+        assert!(json.contains(r#""canvas":{"width":320,"height":200,"#));
+        assert!(json.contains(r#""right":null"#) && !json.contains("inf"));
+    }
+
+    #[test]
+    fn recorded_inputs_replay_identically() {
+        let mut e = Engine::new(0);
+        e.input(Input::Constraints, &[0.0, 0.0, 400.0, 400.0]);
+        e.input(Input::Upsert, &[1.0, 0.0, 0.0, 100.0, 100.0, 0.0, 0.0, 0.0, 2.0, 200.0, 0.0, 50.0, 50.0, 0.0, 0.0, 0.0]);
+        e.input(Input::TapParams, &[0.2, 4.0, 0.3, 0.2, 0.4, 3.0]);
+        e.input(Input::Frame, &[0.016]);
+        e.input(Input::Select, &int_args(&[2]));
+        // Mid-gesture state isn't in the snapshot, so recording waits for the engine to be at rest
+        e.input(Input::Pointers, &[1.0, 50.0, 50.0, 0.5, 1.0]);
+        assert!(!e.start_recording() && e.recorder.is_none());
+        e.input(Input::Pointers, &[1.0, 50.0, 50.0, 0.0, 0.0]);
+        e.input(Input::Frame, &[0.5]);
+        e.events.take_frame();
+        assert!(e.start_recording());
+        let mut frames = Vec::new();
+        let mut frame = |e: &mut Engine, dt: f32| {
+            e.input(Input::Frame, &[dt]);
+            frames.push((e.transforms(), e.events.take_frame()));
+        };
+        // Throw node 1, tap node 2 twice, and change settings along the way
+        for i in 0..6 {
+            e.input(Input::Pointers, &[1.0, 50.0 + 30.0 * i as f32, 50.0, 0.5, 1.0]);
+            frame(&mut e, 0.016);
+        }
+        e.input(Input::Pointers, &[1.0, 200.0, 50.0, 0.0, 0.0]);
+        e.input(Input::Constraints, &[0.0, 0.0, 400.0, 400.0, 1.0, 1.0, 0.5, 0.2]);
+        for _ in 0..3 { frame(&mut e, 0.033); }
+        for _ in 0..2 {
+            e.input(Input::Pointers, &[2.0, 220.0, 20.0, 0.5, 1.0]);
+            frame(&mut e, 0.016);
+            e.input(Input::Pointers, &[2.0, 220.0, 20.0, 0.0, 0.0]);
+            frame(&mut e, 0.016);
+        }
+        // Patch node 1 back, then drag it with node 2 (selected before recording) carried along
+        e.input(Input::Patch, &[int_arg((PATCH_POSITION | PATCH_VELOCITY) as i32), 1.0, 0.0, 100.0, 0.0, 0.0]);
+        e.input(Input::Select, &int_args(&[1]));
+        let x2 = e.nodes[e.index[&2]].x;
+        for i in 0..4 {
+            e.input(Input::Pointers, &[3.0, 50.0 + 10.0 * i as f32, 150.0, 0.5, 1.0]);
+            frame(&mut e, 0.016);
+        }
+        e.input(Input::Pointers, &[3.0, 80.0, 150.0, 0.0, 0.0]);
+        assert_eq!(e.nodes[e.index[&2]].x, x2 + 30.0, "node 2 rode along");
+        e.input(Input::ToggleSelection, &int_args(&[2]));
+        // Camera calls and an undone stroke with an id and color past f32 precision
+        e.input(Input::ZoomAt, &[1.5, 100.0, 100.0]);
+        for _ in 0..3 {
+            e.input(Input::Pan, &[5.0, 2.0]);
+            frame(&mut e, 0.016);
+        }
+        e.input(Input::ReleasePan, &[]);
+        let (path, color) = (1_700_000_123, 0xFF33_66CCu32 as i32);
+        e.input(Input::StartPath, &[int_arg(path), 10.0, 10.0, 1.0, int_arg(color), 2.0]);
+        e.input(Input::AddPoint, &[int_arg(path), 20.0, 15.0, 1.0]);
+        e.input(Input::FinishPath, &[int_arg(path), 0.0]);
+        assert_eq!(e.draw_paths[&path].color, 0xFF33_66CC);
+        frame(&mut e, 0.016);
+        e.input(Input::StartPath, &[int_arg(path + 1), 0.0, 0.0, 1.0, int_arg(color), 2.0]);
+        e.input(Input::FinishPath, &[int_arg(path + 1), 0.0]);
+        assert_eq!(e.input(Input::Undo, &[]), 1);
+        e.input(Input::ViewParams, &[2.0, -10.0, 0.0, 1.0]);
+        for _ in 0..30 { frame(&mut e, 0.016); }
+        let bytes = e.stop_recording();
+        assert!(e.recorder.is_none());
+        assert!(frames.iter().any(|(_, (ints, _))| ints.len() > 4), "the session produced events");
+
+        let mut f = Engine::new(0);
+        f.input(Input::TapParams, &[1.0, 1.0, 1.0, 1.0, 0.0, 1.0]);
+        f.upsert_nodes(&[9.0, 0.0, 0.0, 10.0, 10.0, 0.0, 0.0, 0.0], 8);
+        assert!(f.load_recording(&bytes));
+        let mut replayed = Vec::new();
+        while f.replay_frame() { replayed.push((f.transforms(), f.events.take_frame())); }
+        assert_eq!(replayed, frames);
+        assert_eq!(f.max_taps, 3);
+        assert_eq!(f.selection, BTreeSet::from([1]));
+        assert_eq!(f.selection, e.selection);
+        assert_eq!(f.draw_paths.keys().collect::<Vec<_>>(), vec![&1_700_000_123]);
+        assert_eq!((f.scale, f.pan_x, f.pan_y), (e.scale, e.pan_x, e.pan_y));
+
+        // Corrupt frame indices and truncated buffers are rejected
+        assert!(!f.load_recording(&bytes[..bytes.len() - 2]));
+        let mut bad = bytes.clone();
+        let n = bad.len();
+        bad[n - 12] = 99; // frame index of the last input
+        assert!(!f.load_recording(&bad));
+    }
+
+    #[test]
+    fn fixed_timestep_is_frame_rate_independent() {
+        let run = |dt: f32, frames: usize| {
+            let mut e = Engine::new(0);
+            e.input(Input::TimestepParams, &[120.0, 8.0]);
+            e.input(Input::Constraints, &[0.0, 0.0, 1000.0, 1000.0, 0.0, 0.0, 1.0, 0.5]);
+            e.upsert_nodes(&[1.0, 0.0, 0.0, 10.0, 10.0, 300.0, 0.0, 0.0], 8);
+            e.particles.push(Particle { x: 50.0, y: 0.0, vx: 0.0, vy: 0.0, r: 2.0, life: 10.0 });
+            for _ in 0..frames { e.input(Input::Frame, &[dt]); }
+            e
+        };
+        // One second at 60 Hz, 120 Hz and 30 Hz lands on the same state
+        let (a, b, c) = (run(1.0 / 60.0, 60), run(1.0 / 120.0, 120), run(1.0 / 30.0, 30));
+        for e in [&b, &c] {
+            assert!((e.time - a.time).abs() < 1e-3);
+            assert!((e.nodes[0].x - a.nodes[0].x).abs() < 0.05);
+            assert!((e.particles[0].y - a.particles[0].y).abs() < 0.05);
+        }
+        // Half a step left over: output sits halfway between the last two steps
+        let mut e = run(1.0 / 60.0, 60);
+        e.input(Input::Frame, &[1.0 / 240.0]);
+        let (_, before, after) = e.lerp_poses[0];
+        assert!((e.transforms()[1] - (before[0] + after[0]) * 0.5).abs() < 1e-3);
+        assert!(after[0] > before[0]);
+        assert_eq!(e.locals()[2], e.transforms()[1], "root locals use the blended pose");
+        let mut g = Engine::new(0);
+        g.input(Input::TimestepParams, &[60.0, 8.0]);
+        g.upsert_nodes(&[1.0, 0.0, 0.0, 10.0, 10.0, 0.0, 0.0, 0.0], 8);
+        g.animate(1, AnimProp::W, 70.0, 1.0, 0, None);
+        g.input(Input::Frame, &[1.0 / 60.0]);
+        g.input(Input::Frame, &[1.0 / 120.0]);
+        assert!((g.sizes()[1] - 10.5).abs() < 1e-3, "sizes blend too");
+        // Nodes moved outside a step show where they are
+        e.upsert_nodes(&[1.0, 5.0, 5.0, 10.0, 10.0, 0.0, 0.0, 0.0], 8);
+        assert_eq!(e.transforms()[1], 5.0);
+        // A long stall runs at most max_substeps
+        let t = e.time;
+        e.input(Input::Frame, &[1.0]);
+        assert!((e.time - t - 8.0 / 120.0).abs() < 1e-4);
+        assert!(e.accumulator < e.fixed_dt);
+    }
+
+    #[test]
+    fn dom_node_drag_sequence_ends_in_drag_end() {
+        // What the React/Svelte DomNode sends: a paused mount upsert, pointer updates, size patches
+        let mut e = Engine::new(0);
+        e.history.paused = true;
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 100.0, 60.0, 0.0, 0.0, 0.0], 8);
+        e.history.paused = false;
+        let kinds = |e: &Engine| e.events.ints.chunks(4).map(|ev| ev[0]).collect::<Vec<_>>();
+        e.apply_pointers(&[5.0, 10.0, 10.0, 0.7, 1.0]);
+        e.apply_pointers(&[5.0, 40.0, 30.0, 0.7, 1.0]);
+        e.patch_nodes(PATCH_SIZE, &[1.0, 120.0, 60.0]);
+        e.step(0.016);
+        e.apply_pointers(&[5.0, 40.0, 30.0, 0.0, 0.0]);
+        assert_eq!(kinds(&e), vec![1, 3, 2]);
+        assert!(!e.nodes[0].grabbing && e.pointers.is_empty());
+        assert_eq!((e.nodes[0].x, e.nodes[0].y, e.nodes[0].w), (30.0, 20.0, 120.0));
+        // Re-upserting a held node keeps the gesture: the drag, its drop hover and the drop survive
+        e.events.clear();
+        e.upsert_drop_zones(&[9.0, 0.0, 0.0, 100.0, 100.0, 0.0, 0.0]);
+        e.apply_pointers(&[5.0, 40.0, 30.0, 0.7, 1.0]);
+        e.step(0.016);
+        e.upsert_nodes(&[1.0, 30.0, 20.0, 140.0, 60.0, 0.0, 0.0, 0.0], 8);
+        assert!(e.nodes[0].grabbing && e.nodes[0].drop_zone == Some(9) && e.nodes[0].w == 140.0);
+        e.apply_pointers(&[5.0, 50.0, 35.0, 0.7, 1.0]);
+        e.step(0.016);
+        e.apply_pointers(&[5.0, 50.0, 35.0, 0.0, 0.0]);
+        assert_eq!(kinds(&e), vec![1, 50, 3, 52, 2]);
+        assert_eq!((e.nodes[0].x, e.nodes[0].y), (40.0, 25.0));
+    }
+
+    #[test]
+    fn group_drag_snaps_as_one_box() {
+        let mut e = Engine::new(0);
+        e.guide_threshold = 5.0;
+        e.upsert_nodes(&[
+            1.0, 0.0, 0.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+            2.0, 200.0, 0.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+            3.0, 500.0, 300.0, 50.0, 50.0, 0.0, 0.0, 0.0,
+        ], 8);
+        e.select(&[1, 2]);
+        e.apply_pointers(&[1.0, 10.0, 10.0, 0.5, 1.0]);
+        // The union box's right edge lands 3px short of node 3's left edge
+        e.apply_pointers(&[1.0, 257.0, 10.0, 0.5, 1.0]);
+        e.step(0.016);
+        assert_eq!((e.nodes[0].x, e.nodes[1].x), (250.0, 450.0), "members keep their offset");
+        assert_eq!(e.guides, vec![0.0, 500.0, 1.0, 3.0], "one guide, reported for the leader");
+    }
+
+    #[test]
+    fn thrown_child_glides_and_settles() {
+        let mut e = Engine::new(0);
+        (e.inertia, e.damping) = (1.0, 0.01);
+        e.upsert_nodes(&[1.0, 0.0, 0.0, 400.0, 400.0, 0.0, 0.0, 0.0, 2.0, 100.0, 100.0, 20.0, 20.0, 0.0, 0.0, 0.0], 8);
+        assert_eq!(e.group(1, &[2]), 1);
+        e.apply_pointers(&[1.0, 105.0, 105.0, 0.5, 1.0]);
+        e.step(0.016);
+        e.apply_pointers(&[1.0, 125.0, 105.0, 0.5, 1.0]);
+        e.step(0.016);
+        e.apply_pointers(&[1.0, 125.0, 105.0, 0.0, 0.0]);
+        assert!(e.nodes[1].vx > 0.0);
+        for _ in 0..400 { e.step(0.016); }
+        let (x, y) = (e.nodes[1].x, e.nodes[1].y);
+        assert!(x > 120.0 && y == 100.0, "{x}, {y}");
+        assert_eq!((e.nodes[1].vx, e.nodes[1].vy), (0.0, 0.0));
+        assert!(!e.history.gesture && e.history.undo.len() == 1, "the throw's step closed");
+        // The glide went into the local offset, so the child still rides with its parent
+        e.patch_nodes(PATCH_POSITION, &[1.0, 50.0, 0.0]);
+        e.step(0.016);
+        assert_eq!(e.nodes[1].x, x + 50.0);
+    }
+
+    #[test]
+    fn autoscroll_does_not_count_as_pointer_travel() {
+        let mut e = Engine::new(0);
+        e.left = f32::NEG_INFINITY; e.top = f32::NEG_INFINITY;
+        e.inertia = 1.0;
+        e.long_press_s = 0.5;
+        e.view_w = 400.0; e.view_h = 300.0;
+        e.upsert_nodes(&[1.0, 0.0, 100.0, 50.0, 50.0, 0.0, 0.0, 0.0], 8);
+        // A finger held still inside the left margin scrolls the view under it
+        e.apply_pointers(&[1.0, 10.0, 120.0, 0.5, 1.0]);
+        for _ in 0..6 { e.step(0.1); }
+        assert!(e.pan_x > 100.0 && e.nodes[0].x < -100.0, "the node follows the scroll");
+        let p = &e.pointers[&1];
+        assert!(p.max_move < 1e-3 && p.samples.velocity(e.time, e.throw_window_s) == (0.0, 0.0));
+        assert!(e.events.ints.chunks(4).any(|ev| ev[0] == EventKind::LongPress as i32), "still a long press");
+        e.apply_pointers(&[1.0, 10.0, 120.0, 0.0, 0.0]);
+        assert_eq!((e.nodes[0].vx, e.nodes[0].vy), (0.0, 0.0), "no throw from the scroll");
+    }
+
     #[test]
     #[ignore]
     fn perf_smoke_fps_estimate() {
@@ -800,8 +5501,8 @@ mod tests {
             e.p_damping = 0.02;
             e.restitution = 0.2;
             for i in 0..n {
-                let x = (i % 800) as f32 as f32;
-                let y = (i / 800) as f32 as f32;
+                let x = (i % 800) as f32;
+                let y = (i / 800) as f32;
                 e.particles.push(Particle { x, y, vx: 10.0, vy: -5.0, r: 1.0, life: 10.0 });
             }
             let dt = 1.0 / 60.0;
@@ -826,67 +5527,12 @@ pub fn resize_image_mode(id: i32, out_w: u32, out_h: u32, mode: u32) -> Uint8Arr
     ENGINE.with(|e| {
         if let Some(ref eng) = *e.borrow() {
             if let Some(img) = eng.images.get(&id) {
-                let sw = img.w.max(1);
-                let sh = img.h.max(1);
-                let ow = out_w.max(1);
-                let oh = out_h.max(1);
-                let mut dst = vec![0u8; (ow as usize) * (oh as usize) * 4];
-
-                if mode == 1 {
-                    // Bilinear sampling
-                    let sw_f = sw as f32;
-                    let sh_f = sh as f32;
-                    let ow_f = ow as f32;
-                    let oh_f = oh as f32;
-                    for y in 0..oh {
-                        let gy = (y as f32) * sh_f / oh_f;
-                        let y0 = gy.floor() as i32;
-                        let y1 = (y0 + 1).min(sh as i32 - 1).max(0);
-                        let wy = gy - (y0 as f32);
-                        let y0u = y0.max(0) as usize;
-                        let y1u = y1 as usize;
-                        for x in 0..ow {
-                            let gx = (x as f32) * sw_f / ow_f;
-                            let x0 = gx.floor() as i32;
-                            let x1 = (x0 + 1).min(sw as i32 - 1).max(0);
-                            let wx = gx - (x0 as f32);
-
-                            let x0u = x0.max(0) as usize;
-                            let x1u = x1 as usize;
-                            let i00 = ((y0u * sw as usize) + x0u) * 4;
-                            let i10 = ((y0u * sw as usize) + x1u) * 4;
-                            let i01 = ((y1u * sw as usize) + x0u) * 4;
-                            let i11 = ((y1u * sw as usize) + x1u) * 4;
-                            let di = ((y as usize) * (ow as usize) + (x as usize)) * 4;
-
-                            for ch in 0..4 {
-                                let c00 = img.data[i00 + ch] as f32;
-                                let c10 = img.data[i10 + ch] as f32;
-                                let c01 = img.data[i01 + ch] as f32;
-                                let c11 = img.data[i11 + ch] as f32;
-                                let c0 = c00 + (c10 - c00) * wx;
-                                let c1 = c01 + (c11 - c01) * wx;
-                                let val = c0 + (c1 - c0) * wy;
-                                let mut v = val.round();
-                                if v < 0.0 { v = 0.0; }
-                                if v > 255.0 { v = 255.0; }
-                                dst[di + ch] = v as u8;
-                            }
-                        }
-                    }
+                let dst = if mode == 1 {
+                    resize_bilinear_rgba(&img.data, img.w, img.h, out_w, out_h)
                 } else {
                     // Nearest-neighbor (same as resize_image)
-                    for y in 0..oh {
-                        let sy = (y as u64 * sh as u64) / oh as u64;
-                        for x in 0..ow {
-                            let sx = (x as u64 * sw as u64) / ow as u64;
-                            let si = ((sy as usize) * (sw as usize) + (sx as usize)) * 4;
-                            let di = ((y as usize) * (ow as usize) + (x as usize)) * 4;
-                            dst[di..di+4].copy_from_slice(&img.data[si..si+4]);
-                        }
-                    }
-                }
-
+                    resize_nearest_rgba(&img.data, img.w, img.h, out_w, out_h)
+                };
                 let arr = Uint8Array::new_with_length(dst.len() as u32);
                 arr.copy_from(&dst[..]);
                 out = arr;
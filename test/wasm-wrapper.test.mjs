@@ -30,3 +30,22 @@ test('wasm wrapper: particle APIs are no-ops without pkg', () => {
   assert.doesNotThrow(() => wasm.clearParticles());
   assert.doesNotThrow(() => wasm.setParticleParams([0, 1000, 0.5, 0.5]));
 });
+
+test('wasm wrapper: decodeEvents() reads the header and float payloads', () => {
+  const { EVENT_SCHEMA_VERSION, EventType } = wasm;
+  const events = new Int32Array([0, EVENT_SCHEMA_VERSION, 4, 2, EventType.DRAG_START, 7, 0, 0, EventType.SWIPE, -1, 3, 900]);
+  const data = new Float32Array([0, 0, 0, 0, 10, 20, 12, 24, 0, -900, 0, 0]);
+  const out = wasm.decodeEvents(events, data);
+  assert.equal(out.length, 2);
+  assert.deepEqual(out[0], { type: 1, name: 'drag_start', a: 7, b: 0, data: 0, payload: [10, 20, 12, 24] });
+  assert.equal(out[1].name, 'swipe');
+  assert.deepEqual(out[1].payload, [0, -900, 0, 0]);
+  assert.deepEqual(wasm.decodeEvents(new Int32Array([0, EVENT_SCHEMA_VERSION, 4, 0])), []);
+  assert.throws(() => wasm.decodeEvents(new Int32Array([0, EVENT_SCHEMA_VERSION + 1, 4, 0])), /schema/);
+});
+
+test('wasm wrapper: coordinate conversions are the identity without pkg', () => {
+  assert.deepEqual(wasm.screenToWorld(12, 34), { x: 12, y: 34 });
+  assert.deepEqual(wasm.worldToScreen(12, 34), { x: 12, y: 34 });
+  assert.deepEqual(Array.from(wasm.screenToWorldBatch([1, 2, 3, 4, 5])), [1, 2, 3, 4]);
+});